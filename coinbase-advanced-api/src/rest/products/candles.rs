@@ -1,7 +1,10 @@
 use core::fmt;
 use std::{borrow::Cow, ops::Deref};
 
+use anyhow::Context;
 use derive_builder::Builder;
+use derive_getters::Getters;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 use crate::rest::{endpoint::Endpoint, params::QueryParams};
@@ -14,6 +17,42 @@ pub struct Candles<'a> {
     granularity: Granularity,
 }
 
+#[derive(Debug, Getters, Deserialize)]
+pub struct CandlesResponse {
+    pub candles: Vec<ProductCandle>,
+}
+
+#[derive(Debug, Getters, Deserialize)]
+pub struct ProductCandle {
+    /// Unix timestamp in seconds, as a string.
+    pub start: String,
+    pub low: Decimal,
+    pub high: Decimal,
+    pub open: Decimal,
+    pub close: Decimal,
+    /// Volume in base currency.
+    pub volume: Decimal,
+}
+
+impl ProductCandle {
+    pub fn start_time(&self) -> anyhow::Result<chrono::DateTime<chrono::Utc>> {
+        let timestamp: i64 = self
+            .start
+            .parse()
+            .context(format!("Parsing candle start={}", self.start))?;
+
+        return chrono::DateTime::from_timestamp(timestamp, 0)
+            .context(format!("Invalid candle start={timestamp}"));
+    }
+
+    /// Volume in quote currency. Candles only have the base volume, so it is
+    /// estimated from the typical price.
+    pub fn quote_volume(&self) -> Decimal {
+        let typical_price = (self.high + self.low + self.close) / Decimal::from(3);
+        return self.volume * typical_price;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Granularity {
@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Channel, EventType};
+
+/// Order book updates, received on the `l2_data` channel.
+#[derive(Debug, Builder)]
+pub struct Level2Channel<'a> {
+    product_id: Cow<'a, str>,
+}
+
+#[derive(Debug, Getters, Serialize, Deserialize)]
+pub struct Level2Event {
+    pub r#type: EventType,
+    pub product_id: String,
+    pub updates: Vec<Level2Update>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level2Side {
+    Bid,
+    Offer,
+}
+
+/// New quantity resting at a price level, 0 when the level was removed.
+#[derive(Debug, Getters, Serialize, Deserialize)]
+pub struct Level2Update {
+    pub side: Level2Side,
+    pub event_time: chrono::DateTime<chrono::Utc>,
+    pub price_level: Decimal,
+    pub new_quantity: Decimal,
+}
+
+impl<'a> Channel for Level2Channel<'a> {
+    fn name(&self) -> Cow<'_, str> {
+        return Cow::Borrowed("level2");
+    }
+
+    fn product_id(&self) -> Cow<'_, str> {
+        return self.product_id.clone();
+    }
+}
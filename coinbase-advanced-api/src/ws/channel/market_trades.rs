@@ -0,0 +1,46 @@
+use std::borrow::Cow;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Channel, EventType};
+
+#[derive(Debug, Builder)]
+pub struct MarketTradesChannel<'a> {
+    product_id: Cow<'a, str>,
+}
+
+#[derive(Debug, Getters, Serialize, Deserialize)]
+pub struct MarketTradesEvent {
+    pub r#type: EventType,
+    pub trades: Vec<MarketTrade>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Getters, Serialize, Deserialize)]
+pub struct MarketTrade {
+    pub trade_id: String,
+    pub product_id: String,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub side: Side,
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'a> Channel for MarketTradesChannel<'a> {
+    fn name(&self) -> Cow<'_, str> {
+        return Cow::Borrowed("market_trades");
+    }
+
+    fn product_id(&self) -> Cow<'_, str> {
+        return self.product_id.clone();
+    }
+}
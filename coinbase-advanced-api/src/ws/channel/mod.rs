@@ -1,3 +1,5 @@
+pub mod level2;
+pub mod market_trades;
 pub mod ticker;
 pub mod ticker_batch;
 
@@ -1,9 +1,11 @@
-use std::{borrow::Cow, future};
+use std::borrow::Cow;
 
 use anyhow::Context;
 use coinbase_advanced_api::{
     ws::{
         channel::{
+            level2::{Level2Channel, Level2ChannelBuilder, Level2Event},
+            market_trades::{MarketTradesChannel, MarketTradesChannelBuilder, MarketTradesEvent},
             ticker::{TickerChannel, TickerChannelBuilder, TickerEvent},
             Channel, Response,
         },
@@ -11,8 +13,108 @@ use coinbase_advanced_api::{
     },
     WsClient,
 };
-use futures::StreamExt;
+use futures::{stream::SplitStream, StreamExt};
 use redis::Commands;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Redis channel on which the product id is published when messages were missed.
+const MARKET_DATA_GAP_CHANNEL: &str = "market_data_gap";
+
+/// Forwards every message of a websocket channel to the redis channel of the same name.
+///
+/// Every message of the connection, heartbeats included, increments `sequence_num`.
+/// When a sequence number is skipped, the product id is published on
+/// `market_data_gap` so the data-processor can backfill the missed candles, or
+/// with `resync_on_gap`, forwarding stops so the caller can subscribe again and
+/// receive a new snapshot.
+///
+/// Forwarded messages are printed with `print_messages`, which is too verbose
+/// for level2.
+async fn forward_to_redis<C, T>(
+    mut stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    mut redis_conn: redis::Connection,
+    channel: &'static str,
+    product_id: &'static str,
+    resync_on_gap: bool,
+    print_messages: bool,
+) where
+    C: Channel,
+    T: DeserializeOwned + Serialize,
+{
+    let mut last_sequence_num: Option<u64> = None;
+    // Returns whether forwarding should go on.
+    let mut publish = |message: tokio_tungstenite::tungstenite::Message| -> anyhow::Result<bool> {
+        let raw: serde_json::Value = C::parse(message.clone())
+            .context(format!("Parsing {channel} message to json"))?;
+        if let Some(sequence_num) = raw.get("sequence_num").and_then(|x| x.as_u64()) {
+            if last_sequence_num.is_some_and(|x| sequence_num > x + 1) {
+                println!("{channel} gap: sequence_num {last_sequence_num:?} -> {sequence_num}");
+                if resync_on_gap {
+                    return Ok(false);
+                }
+                let _: () = redis_conn
+                    .publish(MARKET_DATA_GAP_CHANNEL, product_id)
+                    .context(format!("Publishing to redis {MARKET_DATA_GAP_CHANNEL} channel"))?;
+            }
+            last_sequence_num = Some(sequence_num);
+        }
+        if raw.get("channel").and_then(|x| x.as_str()) != Some(channel) {
+            return Ok(true);
+        }
+
+        let response: Response<T> =
+            C::parse(message).context(format!("Parsing {channel} message"))?;
+        let json_message = serde_json::to_string::<Response<T>>(&response)?;
+        if print_messages {
+            println!("{json_message}");
+        }
+        let _: () = redis_conn
+            .publish(channel.to_owned(), json_message)
+            .context(format!("Publishing to redis {channel} channel"))?;
+        Ok(true)
+    };
+    while let Some(x) = stream.next().await {
+        let res = match x.context("Received from websocket") {
+            Ok(message) => publish(message),
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(err) => println!("{err:#}"),
+        }
+    }
+}
+
+/// Forwards the order book updates of `product_id` on `l2_data`, subscribing
+/// again after a gap so that the order book is rebuilt from a new snapshot.
+async fn forward_level2(
+    mut client: WsClient,
+    redis_client: redis::Client,
+    product_id: &'static str,
+) -> anyhow::Result<()> {
+    let level2 = Level2ChannelBuilder::default()
+        .product_id(Cow::Borrowed(product_id))
+        .build()?;
+    loop {
+        let level2_stream = client.subscribe(&level2).await?;
+        forward_to_redis::<Level2Channel, Level2Event>(
+            level2_stream,
+            redis_client.get_connection()?,
+            "l2_data",
+            product_id,
+            true,
+            false,
+        )
+        .await;
+        // The connection may already be closed after a disconnection.
+        if let Err(err) = client.unsubscribe(&level2).await {
+            println!("{err:#}");
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -23,36 +125,40 @@ async fn main() -> anyhow::Result<()> {
     let btc_usd_ticker = TickerChannelBuilder::default()
         .product_id(Cow::Borrowed("BTC-USD"))
         .build()?;
+    let btc_usd_market_trades = MarketTradesChannelBuilder::default()
+        .product_id(Cow::Borrowed("BTC-USD"))
+        .build()?;
     let redis_url = std::env::var("REDIS_URL").context("REDIS_URL from .env file")?;
     let redis_client = redis::Client::open(redis_url)?;
-    let mut redis_conn = redis_client.get_connection()?;
 
-    let stream = client.subscribe(&btc_usd_ticker).await?;
-    let wef = tokio::spawn(async move {
-        let mut publish = |message: tokio_tungstenite::tungstenite::Message| -> anyhow::Result<()> {
-            let ticker = TickerChannel::parse(message).context("Parsing TickerChannel message")?;
-            let json_message = serde_json::to_string::<Response<TickerEvent>>(&ticker)?;
-            println!("{json_message}");
-            let _: () = redis_conn
-                .publish("ticker".to_owned(), json_message)
-                .context("Publishing to redis ticker channel")?;
-            Ok(())
-        };
-        stream
-            .for_each(|x| {
-                let res = match x.context("Received from websocket") {
-                    Ok(message) => publish(message),
-                    Err(err) => Err(err),
-                };
-                if let Err(err) = res {
-                    println!("{err:#}");
-                }
-                future::ready(())
-            })
-            .await;
-    });
+    let ticker_stream = client.subscribe(&btc_usd_ticker).await?;
+    let ticker = tokio::spawn(forward_to_redis::<TickerChannel, TickerEvent>(
+        ticker_stream,
+        redis_client.get_connection()?,
+        "ticker",
+        "BTC-USD",
+        false,
+        true,
+    ));
+    let market_trades_stream = client.subscribe(&btc_usd_market_trades).await?;
+    let market_trades = tokio::spawn(forward_to_redis::<MarketTradesChannel, MarketTradesEvent>(
+        market_trades_stream,
+        redis_client.get_connection()?,
+        "market_trades",
+        "BTC-USD",
+        false,
+        true,
+    ));
+    // Level2 has its own connection, resubscribed on gaps.
+    let level2 = tokio::spawn(forward_level2(
+        WsClient::new(&api_key, &private_key)?,
+        redis_client.clone(),
+        "BTC-USD",
+    ));
     // tokio::time::sleep(tokio::time::Duration::new(20, 0)).await;
     // client.unsubscribe(&btc_usd_ticker).await?;
-    wef.await?;
+    ticker.await?;
+    market_trades.await?;
+    level2.await??;
     return Ok(());
 }
@@ -0,0 +1,179 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use anyhow::Context;
+use coinbase_advanced_api::rest::{
+    client::RestClient,
+    products::candles::{CandlesBuilder, CandlesResponse, Granularity, ProductCandle},
+    query::Query,
+};
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use models::{candle::CandleBuilder, Candle};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use types::Timeframe;
+
+use crate::{
+    candle_store::{Applied, CandleStore},
+    config::Config,
+    correction::{correct_closed_candles, LateData},
+    publisher::publish_updates,
+};
+
+/// Maximum number of 1 minute candles requested at once, Coinbase returns at most 350.
+const BACKFILL_WINDOW_MINUTES: i64 = 300;
+
+/// Fills the candles of `pair` missed while no tick was received, from the
+/// 1 minute candles of the exchange.
+///
+/// The missing range starts at the first whole minute after the last aggregated
+/// data and ends at the start of the current minute. Candles closed by the
+/// backfill are published on `candle_close` in order, marked as `backfilled`.
+/// Candles already closed by the close scheduler during the gap are corrected.
+///
+/// `candle_store` is only locked to read the last data time and to merge the
+/// fetched candles, so ticks keep being aggregated during the requests.
+pub async fn backfill_pair(
+    pair: &str,
+    rest_client: &RestClient,
+    candle_store: &Mutex<CandleStore>,
+    config: &Config,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+) -> anyhow::Result<()> {
+    let from = match candle_store.lock().await.last_data_time(pair) {
+        Some(from) => from,
+        None => return Ok(()),
+    };
+    let (from_minute, _) = Timeframe::Minute(1).open_and_size(&from)?;
+    let from = if from_minute < from {
+        from_minute + chrono::Duration::minutes(1)
+    } else {
+        from
+    };
+    let (to, _) = Timeframe::Minute(1).open_and_size(&chrono::Utc::now())?;
+    if from >= to {
+        return Ok(());
+    }
+    info!("Backfilling {pair} candles from {from} to {to}");
+    let candles = fetch_candles(pair, rest_client, from, to).await?;
+
+    let candle_store = &mut *candle_store.lock().await;
+    let mut closed_candles = Vec::new();
+    let mut late_candles = Vec::new();
+    for candle in candles.iter() {
+        for timeframe in config.timeframes(pair) {
+            match candle_store.merge(timeframe, candle)? {
+                Applied::Updated(update) => closed_candles.extend(update.closed),
+                Applied::Duplicate => (),
+                Applied::Closed => late_candles.push((timeframe, candle.clone())),
+            }
+        }
+    }
+
+    closed_candles.sort_by_key(|x| x.close_time());
+    info!(
+        "Backfilled {pair} candles from {from} to {to}, {} candles closed",
+        closed_candles.len()
+    );
+    publish_updates(
+        &closed_candles,
+        &[],
+        candle_store,
+        config,
+        redis_pool.clone(),
+        pg_pool.clone(),
+        false,
+    )?;
+    let late: Vec<_> = late_candles
+        .iter()
+        .map(|(timeframe, candle)| (*timeframe, LateData::Candle(candle)))
+        .collect();
+    return correct_closed_candles(&late, redis_pool, pg_pool, false);
+}
+
+/// 1 minute candles of `pair` opened from `from` to `to`, oldest first.
+async fn fetch_candles(
+    pair: &str,
+    rest_client: &RestClient,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<Vec<Candle>> {
+    let mut candles = Vec::new();
+    let mut start = from;
+    while start < to {
+        let end = to.min(start + chrono::Duration::minutes(BACKFILL_WINDOW_MINUTES));
+        let request = CandlesBuilder::default()
+            .product_id(Cow::Borrowed(pair))
+            .start(start)
+            .end(end)
+            .granularity(Granularity::OneMinute)
+            .build()?;
+        let mut response: CandlesResponse = request
+            .query(rest_client)
+            .await
+            .context(format!("Fetching {pair} candles from {start} to {end}"))?;
+        response.candles.sort_by_key(|x| x.start.clone());
+
+        for product_candle in response.candles.iter() {
+            let candle = to_backfilled_candle(pair, product_candle)?;
+            // `end` is inclusive for Coinbase, the next window fetches it.
+            if *candle.open_time() < start || *candle.open_time() >= end {
+                continue;
+            }
+            candles.push(candle);
+        }
+        start = end;
+    }
+    return Ok(candles);
+}
+
+/// Backfills every pair known to `candle_store` or configured. A pair that
+/// cannot be backfilled, e.g. while the exchange is unreachable, is logged and
+/// skipped, so live ticks are still applied.
+pub async fn backfill_all(
+    rest_client: &RestClient,
+    candle_store: &Mutex<CandleStore>,
+    config: &Config,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+) {
+    let pairs: HashSet<String> = candle_store
+        .lock()
+        .await
+        .pairs()
+        .chain(config.products().cloned())
+        .collect();
+
+    for pair in pairs.iter() {
+        let res = backfill_pair(
+            pair,
+            rest_client,
+            candle_store,
+            config,
+            redis_pool.clone(),
+            pg_pool.clone(),
+        )
+        .await
+        .context(format!("Backfilling {pair} on startup"));
+        if let Err(err) = res {
+            error!("{err:#}");
+        }
+    }
+}
+
+fn to_backfilled_candle(pair: &str, product_candle: &ProductCandle) -> anyhow::Result<Candle> {
+    let mut candle = CandleBuilder::default()
+        .pair(pair.to_owned())
+        .open_time(product_candle.start_time()?)
+        .timeframe(Timeframe::Minute(1).to_string())
+        .open(product_candle.open)
+        .high(product_candle.high)
+        .low(product_candle.low)
+        .close(product_candle.close)
+        .size_in_millis(chrono::Duration::minutes(1).num_milliseconds())
+        .backfilled(true)
+        .build()?;
+    candle.add_volume(product_candle.volume, product_candle.quote_volume(), 0);
+
+    return Ok(candle);
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use models::{candle::CandleBuilder, Candle};
+use rust_decimal::Decimal;
+use types::BarType;
+
+use crate::candle_store::Tick;
+
+type BarKey = (String, String);
+
+/// Builds the bars that are not aggregated on a fixed time period.
+///
+/// Range, renko, tick and volume bars are built from trades, so only live,
+/// Heikin-Ashi candles from the closed candles of their time frame. Only closed
+/// bars are stored, a bar being built is lost on restart.
+#[derive(Default)]
+pub struct BarBuilder {
+    forming: HashMap<BarKey, Candle>,
+    last: HashMap<BarKey, Candle>,
+}
+
+impl BarBuilder {
+    /// Resumes from the last stored bar of every (pair, bar type).
+    pub fn new(last_bars: Vec<Candle>) -> Self {
+        let last = last_bars
+            .into_iter()
+            .map(|x| ((x.pair().to_owned(), x.timeframe().to_owned()), x))
+            .collect();
+
+        return Self {
+            last,
+            ..Default::default()
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.forming.clear();
+        self.last.clear();
+    }
+
+    /// Applies a trade to the bar of `bar_type` being built for `tick.pair`,
+    /// returning the bars it closed.
+    ///
+    /// Ticks without a size and Heikin-Ashi bar types are ignored.
+    pub fn update(&mut self, bar_type: &BarType, tick: &Tick) -> anyhow::Result<Vec<Candle>> {
+        let size = match (bar_type, tick.size) {
+            (BarType::HeikinAshi(_), _) | (_, None) => return Ok(Vec::new()),
+            (_, Some(size)) => size,
+        };
+        let key = (tick.pair.to_owned(), bar_type.to_string());
+
+        // A trade that would stretch a range bar past its range opens the next bar.
+        let mut closed = Vec::new();
+        if let (BarType::Range(range), Some(bar)) = (bar_type, self.forming.get(&key)) {
+            if bar.high().max(&tick.price) - bar.low().min(&tick.price) > *range {
+                closed.extend(self.close_forming(&key));
+            }
+        }
+
+        let mut bar = match self.forming.remove(&key) {
+            Some(bar) => bar,
+            None => self.open_bar(&key, bar_type, tick)?,
+        };
+        bar.apply_tick(tick.price, Some(size), tick.timestamp, tick.sequence);
+        bar.extend_to(tick.timestamp);
+
+        match bar_type {
+            BarType::Tick(count) if bar.trade_count() >= count => closed.push(bar),
+            BarType::Volume(volume) if bar.volume() >= volume => closed.push(bar),
+            BarType::Renko(brick) => closed.extend(self.renko_bricks(&key, bar, *brick)?),
+            _ => {
+                self.forming.insert(key.clone(), bar);
+            }
+        }
+
+        if let Some(last) = closed.last() {
+            self.last.insert(key, last.clone());
+        }
+        return Ok(closed);
+    }
+
+    /// Heikin-Ashi candle of a closed time candle, when `bar_type` is the
+    /// Heikin-Ashi bar type of the candle's time frame.
+    pub fn heikin_ashi(
+        &mut self,
+        bar_type: &BarType,
+        candle: &Candle,
+    ) -> anyhow::Result<Option<Candle>> {
+        match bar_type {
+            BarType::HeikinAshi(timeframe) if timeframe.to_string() == *candle.timeframe() => (),
+            _ => return Ok(None),
+        }
+        let key = (candle.pair().to_owned(), bar_type.to_string());
+        let close =
+            (candle.open() + candle.high() + candle.low() + candle.close()) / Decimal::from(4);
+        let open = match self.last.get(&key) {
+            Some(previous) => (previous.open() + previous.close()) / Decimal::TWO,
+            None => (candle.open() + candle.close()) / Decimal::TWO,
+        };
+
+        let mut bar = CandleBuilder::default()
+            .pair(candle.pair().to_owned())
+            .open_time(candle.open_time().to_owned())
+            .timeframe(bar_type.to_string())
+            .open(open)
+            .high((*candle.high()).max(open).max(close))
+            .low((*candle.low()).min(open).min(close))
+            .close(close)
+            .size_in_millis(candle.size_in_millis().to_owned())
+            .synthetic(candle.synthetic().to_owned())
+            .backfilled(candle.backfilled().to_owned())
+            .bar_type(bar_type.tag().to_owned())
+            .build()?;
+        bar.add_volume(
+            candle.volume().to_owned(),
+            candle.quote_volume().to_owned(),
+            candle.trade_count().to_owned(),
+        );
+        self.last.insert(key, bar.clone());
+        return Ok(Some(bar));
+    }
+
+    fn close_forming(&mut self, key: &BarKey) -> Option<Candle> {
+        let bar = self.forming.remove(key)?;
+        self.last.insert(key.clone(), bar.clone());
+        return Some(bar);
+    }
+
+    /// Empty bar opening at `tick`, strictly after the previous bar so every
+    /// bar has its own `open_time`.
+    fn open_bar(&self, key: &BarKey, bar_type: &BarType, tick: &Tick) -> anyhow::Result<Candle> {
+        let open_time = match self.last.get(key) {
+            Some(last) if *last.open_time() >= tick.timestamp => {
+                *last.open_time() + chrono::Duration::milliseconds(1)
+            }
+            _ => tick.timestamp,
+        };
+        let bar = CandleBuilder::default()
+            .pair(tick.pair.to_owned())
+            .open_time(open_time)
+            .timeframe(bar_type.to_string())
+            .open(tick.price)
+            .high(tick.price)
+            .low(tick.price)
+            .close(tick.price)
+            .size_in_millis(0)
+            .bar_type(bar_type.tag().to_owned())
+            .build()?;
+
+        return Ok(bar);
+    }
+
+    /// Bricks completed by the trades of `bar`, or `bar` put back as the bar
+    /// being built when none is.
+    ///
+    /// A brick continues the trend once price moves `brick` past the last brick,
+    /// and reverses it once price moves `brick` past the last brick's open. The
+    /// first brick is formed `brick` away from the first trade.
+    fn renko_bricks(
+        &mut self,
+        key: &BarKey,
+        bar: Candle,
+        brick: Decimal,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let (mut top, mut bottom) = match self.last.get(key) {
+            Some(last) => (
+                (*last.open()).max(*last.close()),
+                (*last.open()).min(*last.close()),
+            ),
+            None => (*bar.open(), *bar.open()),
+        };
+        let mut bricks: Vec<Candle> = Vec::new();
+
+        loop {
+            let (open, close) = if *bar.close() >= top + brick {
+                (top, top + brick)
+            } else if *bar.close() <= bottom - brick {
+                (bottom, bottom - brick)
+            } else {
+                break;
+            };
+            let open_time = match bricks.last() {
+                Some(previous) => *previous.open_time() + chrono::Duration::milliseconds(1),
+                None => *bar.open_time(),
+            };
+            let mut candle = CandleBuilder::default()
+                .pair(bar.pair().to_owned())
+                .open_time(open_time)
+                .timeframe(bar.timeframe().to_owned())
+                .open(open)
+                .high(open.max(close))
+                .low(open.min(close))
+                .close(close)
+                .size_in_millis(0)
+                .bar_type(bar.bar_type().to_owned())
+                .build()?;
+            candle.extend_to(bar.close_time());
+            // The traded volume goes to the first brick, the next ones are formed
+            // by the same trade.
+            if bricks.is_empty() {
+                candle.add_volume(
+                    bar.volume().to_owned(),
+                    bar.quote_volume().to_owned(),
+                    bar.trade_count().to_owned(),
+                );
+            }
+            top = open.max(close);
+            bottom = open.min(close);
+            bricks.push(candle);
+        }
+
+        if bricks.is_empty() {
+            self.forming.insert(key.clone(), bar);
+        }
+        return Ok(bricks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+    use types::{BarType, Timeframe};
+
+    use super::BarBuilder;
+    use crate::candle_store::Tick;
+
+    fn trade(price: i64, second: u32) -> Tick<'static> {
+        return Tick {
+            pair: "BTC-USD",
+            price: Decimal::from(price),
+            size: Some(Decimal::ONE),
+            timestamp: chrono::Utc
+                .with_ymd_and_hms(2024, 1, 1, 0, 0, second)
+                .unwrap(),
+            sequence: None,
+        };
+    }
+
+    #[test]
+    fn range_and_tick_bars() {
+        let mut builder = BarBuilder::default();
+        let range = BarType::Range(Decimal::from(10));
+        let ticks = BarType::Tick(2);
+
+        assert!(builder.update(&range, &trade(100, 0)).unwrap().is_empty());
+        assert!(builder.update(&range, &trade(108, 1)).unwrap().is_empty());
+        let closed = builder.update(&range, &trade(111, 2)).unwrap();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(*closed[0].high(), Decimal::from(108));
+        assert_eq!(closed[0].timeframe(), "range-10");
+        assert_eq!(closed[0].bar_type(), "range");
+
+        assert!(builder.update(&ticks, &trade(100, 0)).unwrap().is_empty());
+        assert!(builder.update(&ticks, &trade(100, 0)).unwrap().is_empty());
+        let closed = builder.update(&ticks, &trade(101, 1)).unwrap();
+        assert_eq!(*closed[0].trade_count(), 2);
+        let closed = builder.update(&ticks, &trade(102, 2)).unwrap();
+        assert!(closed.is_empty());
+    }
+
+    #[test]
+    fn renko_bricks_reverse_after_two_bricks() {
+        let mut builder = BarBuilder::default();
+        let renko = BarType::Renko(Decimal::from(10));
+
+        assert!(builder.update(&renko, &trade(100, 0)).unwrap().is_empty());
+        let bricks = builder.update(&renko, &trade(125, 1)).unwrap();
+        let closes: Vec<_> = bricks.iter().map(|x| *x.close()).collect();
+        assert_eq!(closes, vec![Decimal::from(110), Decimal::from(120)]);
+        assert!(bricks[1].open_time() > bricks[0].open_time());
+
+        assert!(builder.update(&renko, &trade(105, 2)).unwrap().is_empty());
+        let bricks = builder.update(&renko, &trade(100, 3)).unwrap();
+        assert_eq!(*bricks[0].open(), Decimal::from(110));
+        assert_eq!(*bricks[0].close(), Decimal::from(100));
+    }
+
+    #[test]
+    fn heikin_ashi_from_closed_candles() {
+        let mut builder = BarBuilder::default();
+        let bar_type = BarType::HeikinAshi(Timeframe::Hour(1));
+        let candle = models::candle::CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .timeframe("1h".to_owned())
+            .open(Decimal::from(10))
+            .high(Decimal::from(20))
+            .low(Decimal::from(10))
+            .close(Decimal::from(20))
+            .size_in_millis(3_600_000)
+            .build()
+            .unwrap();
+
+        let bar = builder.heikin_ashi(&bar_type, &candle).unwrap().unwrap();
+        assert_eq!(*bar.open(), Decimal::from(15));
+        assert_eq!(*bar.close(), Decimal::from(15));
+        assert_eq!(bar.timeframe(), "ha-1h");
+        let bar = builder.heikin_ashi(&bar_type, &candle).unwrap().unwrap();
+        assert_eq!(*bar.open(), Decimal::from(15));
+        assert!(builder
+            .heikin_ashi(&BarType::HeikinAshi(Timeframe::Hour(4)), &candle)
+            .unwrap()
+            .is_none());
+    }
+}
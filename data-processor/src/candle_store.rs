@@ -0,0 +1,634 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+};
+
+use diesel::{prelude::*, r2d2::ConnectionManager, upsert::excluded, PgConnection};
+use models::{
+    candle::{CandleBuilder, TickSequence},
+    schema::candles,
+    Candle,
+};
+use rust_decimal::Decimal;
+use types::{BarType, Timeframe};
+
+use crate::{bars::BarBuilder, config::Config};
+
+type CandleKey = (String, String);
+
+/// A price from the exchange, with the traded size when it comes from a trade.
+pub struct Tick<'a> {
+    pub pair: &'a str,
+    pub price: Decimal,
+    pub size: Option<Decimal>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Exchange sequence ordering ticks with the same timestamp.
+    pub sequence: Option<TickSequence>,
+}
+
+/// Number of trade ids remembered per pair to drop trades delivered again.
+const RECENT_TRADE_IDS: usize = 10_000;
+
+/// Ids of the last trades applied for a pair.
+#[derive(Default)]
+struct RecentTradeIds {
+    ids: HashSet<i64>,
+    order: VecDeque<i64>,
+    /// Trades up to this id were applied before the store was loaded.
+    floor: Option<i64>,
+}
+
+impl RecentTradeIds {
+    /// Records `id`, returning `false` when it was already applied.
+    fn insert(&mut self, id: i64) -> bool {
+        if self.floor.is_some_and(|x| id <= x) || !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > RECENT_TRADE_IDS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        return true;
+    }
+}
+
+pub struct CandleUpdate {
+    pub closed: Vec<Candle>,
+    pub candle: Box<Candle>,
+}
+
+pub enum Applied {
+    Updated(CandleUpdate),
+    /// Already applied to the open candle.
+    Duplicate,
+    /// Belongs to a candle that is already closed.
+    Closed,
+}
+
+/// Keeps the currently open candle of every (pair, timeframe) in memory so that
+/// ticks are aggregated without querying the database, and persists modified
+/// candles in batches.
+#[derive(Default)]
+pub struct CandleStore {
+    open_candles: HashMap<CandleKey, Candle>,
+    dirty: HashSet<CandleKey>,
+    closed: Vec<Candle>,
+    last_closed: HashMap<CandleKey, chrono::DateTime<chrono::Utc>>,
+    /// Time up to which exchange data was aggregated, per pair.
+    last_data: HashMap<String, chrono::DateTime<chrono::Utc>>,
+    trade_ids: HashMap<String, RecentTradeIds>,
+    bars: BarBuilder,
+}
+
+impl CandleStore {
+    /// Rebuilds the open candles from the latest stored candle of each (pair, timeframe),
+    /// and resumes the other bar types from their latest stored bar.
+    pub fn load(
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Self> {
+        let (last_candles, last_bars): (Vec<Candle>, Vec<Candle>) = candles::table
+            .distinct_on((candles::pair, candles::timeframe))
+            .select(Candle::as_select())
+            .order((candles::pair, candles::timeframe, candles::open_time.desc()))
+            .get_results::<Candle>(pg_conn)?
+            .into_iter()
+            .partition(|x| x.bar_type() == BarType::TIME_TAG);
+        // The trades sent again on subscribe were applied before a restart.
+        let mut trade_ids: HashMap<String, RecentTradeIds> = HashMap::new();
+        for candle in last_candles.iter() {
+            let recent = trade_ids.entry(candle.pair().to_owned()).or_default();
+            recent.floor = recent.floor.max(*candle.last_trade_id());
+        }
+        let open_candles = last_candles
+            .into_iter()
+            .map(|x| ((x.pair().to_owned(), x.timeframe().to_owned()), x))
+            .collect();
+
+        return Ok(Self {
+            open_candles,
+            trade_ids,
+            bars: BarBuilder::new(last_bars),
+            ..Default::default()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.open_candles.clear();
+        self.dirty.clear();
+        self.closed.clear();
+        self.last_closed.clear();
+        self.last_data.clear();
+        self.trade_ids.clear();
+        self.bars.clear();
+    }
+
+    /// Whether `tick` should be applied, `false` for a trade already applied,
+    /// such as the recent trades Coinbase sends on every subscription.
+    ///
+    /// Ticks are accepted once, before being applied to every timeframe and bar.
+    pub fn accept(&mut self, tick: &Tick) -> bool {
+        return match tick.sequence {
+            Some(TickSequence::Trade(id)) => self
+                .trade_ids
+                .entry(tick.pair.to_owned())
+                .or_default()
+                .insert(id),
+            _ => true,
+        };
+    }
+
+    /// Applies a tick to the open candle of `tick.pair` on `timeframe`.
+    pub fn update(&mut self, timeframe: &Timeframe, tick: &Tick) -> anyhow::Result<Applied> {
+        self.record_data_time(tick.pair, tick.timestamp);
+        return self.apply(
+            tick.pair,
+            timeframe,
+            &tick.timestamp,
+            tick.price,
+            |candle| candle.apply_tick(tick.price, tick.size, tick.timestamp, tick.sequence),
+        );
+    }
+
+    /// Aggregates a candle of a lower timeframe, such as a 1 minute candle fetched
+    /// from the exchange, into the open candle of `timeframe`.
+    pub fn merge(&mut self, timeframe: &Timeframe, candle: &Candle) -> anyhow::Result<Applied> {
+        if !candle.synthetic() {
+            self.record_data_time(candle.pair(), candle.close_time());
+        }
+        return self.apply(
+            candle.pair(),
+            timeframe,
+            candle.open_time(),
+            candle.close().to_owned(),
+            |x| {
+                x.merge(candle);
+                return true;
+            },
+        );
+    }
+
+    /// Runs `apply` on the candle of `pair` on `timeframe` containing `timestamp`,
+    /// opening it at `price` if needed. `apply` returns `false` when the data was
+    /// already applied to the candle.
+    ///
+    /// Closing a candle also closes a synthetic flat candle for every period
+    /// without ticks between it and `timestamp`, so the series stays contiguous.
+    fn apply(
+        &mut self,
+        pair: &str,
+        timeframe: &Timeframe,
+        timestamp: &chrono::DateTime<chrono::Utc>,
+        price: Decimal,
+        apply: impl FnOnce(&mut Candle) -> bool,
+    ) -> anyhow::Result<Applied> {
+        let (open_time, size_in_millis) = timeframe.open_and_size(timestamp)?;
+        let key = (pair.to_owned(), timeframe.to_string());
+        let mut closed = Vec::new();
+
+        match self.open_candles.get_mut(&key) {
+            Some(candle) if *candle.open_time() == open_time => {
+                if !apply(candle) {
+                    return Ok(Applied::Duplicate);
+                }
+                let candle = Box::new(candle.clone());
+                self.dirty.insert(key);
+                return Ok(Applied::Updated(CandleUpdate { closed, candle }));
+            }
+            Some(candle) if *candle.open_time() > open_time => return Ok(Applied::Closed),
+            Some(_) => {
+                let mut previous = self.open_candles.remove(&key).unwrap();
+                loop {
+                    let next = synthetic_candle_after(&previous, timeframe)?;
+                    closed.push(previous);
+                    if *next.open_time() >= open_time {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            None if self.last_closed.get(&key).is_some_and(|x| *x >= open_time) => {
+                return Ok(Applied::Closed)
+            }
+            None => (),
+        }
+        if let Some(last) = closed.last() {
+            self.last_closed
+                .insert(key.clone(), last.open_time().to_owned());
+            self.closed.extend(closed.iter().cloned());
+        }
+
+        let mut candle = CandleBuilder::default()
+            .pair(pair.to_owned())
+            .open_time(open_time)
+            .timeframe(timeframe.to_string())
+            .open(price)
+            .high(price)
+            .low(price)
+            .close(price)
+            .size_in_millis(size_in_millis)
+            .synthetic(true)
+            .build()?;
+        apply(&mut candle);
+        self.open_candles.insert(key.clone(), candle.clone());
+        self.dirty.insert(key);
+        return Ok(Applied::Updated(CandleUpdate {
+            closed,
+            candle: Box::new(candle),
+        }));
+    }
+
+    /// Applies a trade to the bar of `bar_type` being built, returning the bars it closed.
+    pub fn update_bar(&mut self, bar_type: &BarType, tick: &Tick) -> anyhow::Result<Vec<Candle>> {
+        let closed = self.bars.update(bar_type, tick)?;
+        self.closed.extend(closed.iter().cloned());
+        return Ok(closed);
+    }
+
+    /// Builds the Heikin-Ashi candles configured for the `closed` time candles.
+    pub fn heikin_ashi(
+        &mut self,
+        closed: &[Candle],
+        config: &Config,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let mut bars = Vec::new();
+        for candle in closed.iter() {
+            for bar_type in config.bars(candle.pair()) {
+                if let Some(bar) = self.bars.heikin_ashi(bar_type, candle)? {
+                    bars.push(bar);
+                }
+            }
+        }
+        self.closed.extend(bars.iter().cloned());
+        return Ok(bars);
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = String> + '_ {
+        return self
+            .open_candles
+            .keys()
+            .map(|(pair, _)| pair.to_owned())
+            .collect::<HashSet<_>>()
+            .into_iter();
+    }
+
+    /// Timeframes with an open candle for `pair`.
+    pub fn timeframes<'a>(&'a self, pair: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        return self
+            .open_candles
+            .keys()
+            .filter(move |(x, _)| x == pair)
+            .map(|(_, timeframe)| timeframe);
+    }
+
+    pub fn open_candle(&self, pair: &str, timeframe: &Timeframe) -> Option<&Candle> {
+        return self
+            .open_candles
+            .get(&(pair.to_owned(), timeframe.to_string()));
+    }
+
+    fn record_data_time(&mut self, pair: &str, time: chrono::DateTime<chrono::Utc>) {
+        self.last_data
+            .entry(pair.to_owned())
+            .and_modify(|x| *x = time.max(*x))
+            .or_insert(time);
+    }
+
+    /// Time up to which exchange data was aggregated for `pair`.
+    ///
+    /// Until data is received, this is the end of the open candle of the finest
+    /// timeframe loaded from database.
+    pub fn last_data_time(&self, pair: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        if let Some(time) = self.last_data.get(pair) {
+            return Some(time.to_owned());
+        }
+        return self
+            .timeframes(pair)
+            .filter_map(|x| Timeframe::from_str(x).ok())
+            .min_by_key(|x| x.nominal_duration())
+            .and_then(|x| self.open_candle(pair, &x))
+            .map(|x| x.close_time());
+    }
+
+    /// Earliest time at which one of the open candles ends.
+    pub fn next_close_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        return self.open_candles.values().map(|x| x.close_time()).min();
+    }
+
+    /// Closes every open candle that ended at or before `until`.
+    ///
+    /// Each closed candle is replaced by a synthetic flat candle for the next
+    /// period, which becomes a regular candle on its first tick or is closed as
+    /// is when its period ends without any.
+    ///
+    /// Closed candles are remembered so later ticks for their period are reported
+    /// as `Applied::Closed` and a close is never reported twice.
+    pub fn close_expired(
+        &mut self,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let expired: Vec<CandleKey> = self
+            .open_candles
+            .iter()
+            .filter(|(_, candle)| candle.close_time() <= until)
+            .map(|(key, _)| key.to_owned())
+            .collect();
+        let mut closed = Vec::with_capacity(expired.len());
+
+        for key in expired {
+            let timeframe = Timeframe::from_str(&key.1)?;
+            let mut candle = self.open_candles.remove(&key).unwrap();
+            loop {
+                let next = synthetic_candle_after(&candle, &timeframe)?;
+                self.last_closed
+                    .insert(key.clone(), candle.open_time().to_owned());
+                self.closed.push(candle.clone());
+                closed.push(candle);
+                if next.close_time() > until {
+                    self.open_candles.insert(key.clone(), next);
+                    self.dirty.insert(key);
+                    break;
+                }
+                candle = next;
+            }
+        }
+        closed.sort_by_key(|x| x.close_time());
+        return Ok(closed);
+    }
+
+    pub fn has_pending_writes(&self) -> bool {
+        return !self.dirty.is_empty() || !self.closed.is_empty();
+    }
+
+    /// Upserts every candle closed or modified since the last flush in a single statement.
+    pub fn flush(
+        &mut self,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<usize> {
+        if !self.has_pending_writes() {
+            return Ok(0);
+        }
+        let values: Vec<&Candle> = self
+            .closed
+            .iter()
+            .chain(
+                self.dirty
+                    .iter()
+                    .filter_map(|key| self.open_candles.get(key)),
+            )
+            .collect();
+        let count = upsert_candles(&values, pg_conn)?;
+        self.closed.clear();
+        self.dirty.clear();
+        return Ok(count);
+    }
+}
+
+/// Inserts `values`, or updates every column of the candles already stored.
+pub fn upsert_candles(
+    values: &[&Candle],
+    pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+) -> anyhow::Result<usize> {
+    let count = diesel::insert_into(candles::table)
+        .values(values.to_owned())
+        .on_conflict((candles::pair, candles::open_time, candles::timeframe))
+        .do_update()
+        .set((
+            candles::open.eq(excluded(candles::open)),
+            candles::high.eq(excluded(candles::high)),
+            candles::low.eq(excluded(candles::low)),
+            candles::close.eq(excluded(candles::close)),
+            candles::synthetic.eq(excluded(candles::synthetic)),
+            candles::volume.eq(excluded(candles::volume)),
+            candles::quote_volume.eq(excluded(candles::quote_volume)),
+            candles::trade_count.eq(excluded(candles::trade_count)),
+            candles::vwap.eq(excluded(candles::vwap)),
+            candles::backfilled.eq(excluded(candles::backfilled)),
+            candles::last_tick_time.eq(excluded(candles::last_tick_time)),
+            candles::last_sequence.eq(excluded(candles::last_sequence)),
+            candles::last_trade_id.eq(excluded(candles::last_trade_id)),
+        ))
+        .execute(pg_conn)?;
+    return Ok(count);
+}
+
+/// Flat candle for the period following `candle`, at `candle`'s close price.
+fn synthetic_candle_after(candle: &Candle, timeframe: &Timeframe) -> anyhow::Result<Candle> {
+    let (open_time, size_in_millis) = timeframe.open_and_size(&candle.close_time())?;
+    let candle = CandleBuilder::default()
+        .pair(candle.pair().to_owned())
+        .open_time(open_time)
+        .timeframe(candle.timeframe().to_owned())
+        .open(candle.close().to_owned())
+        .high(candle.close().to_owned())
+        .low(candle.close().to_owned())
+        .close(candle.close().to_owned())
+        .size_in_millis(size_in_millis)
+        .synthetic(true)
+        .build()?;
+
+    return Ok(candle);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+    use types::Timeframe;
+
+    use models::candle::TickSequence;
+
+    use super::{Applied, CandleStore, CandleUpdate, Tick};
+
+    fn tick(price: i64, timestamp: chrono::DateTime<chrono::Utc>) -> Tick<'static> {
+        return Tick {
+            pair: "BTC-USD",
+            price: Decimal::from(price),
+            size: None,
+            timestamp,
+            sequence: None,
+        };
+    }
+
+    fn updated(applied: anyhow::Result<Applied>) -> CandleUpdate {
+        match applied.unwrap() {
+            Applied::Updated(update) => return update,
+            Applied::Duplicate => panic!("tick should not be a duplicate"),
+            Applied::Closed => panic!("tick should not belong to a closed candle"),
+        }
+    }
+
+    #[test]
+    fn update_closes_previous_candle() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(5);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+
+        let update = updated(store.update(&timeframe, &tick(10, at(0, 1))));
+        assert!(update.closed.is_empty());
+        let update = updated(store.update(&timeframe, &tick(12, at(2, 0))));
+        assert!(update.closed.is_empty());
+        assert_eq!(*update.candle.high(), Decimal::from(12));
+        assert_eq!(*update.candle.low(), Decimal::from(10));
+
+        let update = updated(store.update(&timeframe, &tick(11, at(5, 0))));
+        let closed = update
+            .closed
+            .first()
+            .expect("first candle should be closed");
+        assert_eq!(*closed.open_time(), at(0, 0));
+        assert_eq!(*closed.close(), Decimal::from(12));
+        assert_eq!(*update.candle.open_time(), at(5, 0));
+
+        assert!(matches!(
+            store.update(&timeframe, &tick(9, at(4, 59))).unwrap(),
+            Applied::Closed
+        ));
+        assert!(store.has_pending_writes());
+    }
+
+    #[test]
+    fn close_expired_is_emitted_once() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(1);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+
+        store.update(&timeframe, &tick(10, at(0, 30))).unwrap();
+        assert_eq!(store.next_close_time(), Some(at(1, 0)));
+        assert!(store.close_expired(at(0, 59)).unwrap().is_empty());
+        assert_eq!(store.close_expired(at(1, 2)).unwrap().len(), 1);
+        assert!(store.close_expired(at(1, 3)).unwrap().is_empty());
+
+        assert!(matches!(
+            store.update(&timeframe, &tick(11, at(0, 59))).unwrap(),
+            Applied::Closed
+        ));
+        let update = updated(store.update(&timeframe, &tick(11, at(1, 5))));
+        assert!(update.closed.is_empty());
+        assert!(!update.candle.synthetic());
+        assert_eq!(*update.candle.open(), Decimal::from(11));
+    }
+
+    #[test]
+    fn empty_periods_are_filled() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(1);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+
+        store.update(&timeframe, &tick(10, at(0, 30))).unwrap();
+        let update = updated(store.update(&timeframe, &tick(11, at(3, 30))));
+        let open_times: Vec<_> = update.closed.iter().map(|x| *x.open_time()).collect();
+        assert_eq!(open_times, vec![at(0, 0), at(1, 0), at(2, 0)]);
+        assert!(!update.closed[0].synthetic());
+        assert!(update.closed[1..]
+            .iter()
+            .all(|x| *x.synthetic() && *x.high() == Decimal::from(10)));
+
+        let closed = store.close_expired(at(6, 0)).unwrap();
+        let open_times: Vec<_> = closed.iter().map(|x| *x.open_time()).collect();
+        assert_eq!(open_times, vec![at(3, 0), at(4, 0), at(5, 0)]);
+        assert_eq!(store.next_close_time(), Some(at(7, 0)));
+    }
+
+    #[test]
+    fn trades_and_merged_candles_add_volume() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(5);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+
+        let mut trade = tick(10, at(0, 1));
+        trade.size = Some(Decimal::from(2));
+        store.update(&timeframe, &trade).unwrap();
+        let mut trade = tick(20, at(0, 2));
+        trade.size = Some(Decimal::from(3));
+        let candle = updated(store.update(&timeframe, &trade)).candle;
+        assert_eq!(*candle.volume(), Decimal::from(5));
+        assert_eq!(*candle.quote_volume(), Decimal::from(80));
+        assert_eq!(*candle.trade_count(), 2);
+        assert_eq!(*candle.vwap(), Some(Decimal::from(16)));
+
+        let mut minute = models::candle::CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(at(1, 0))
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(20))
+            .high(Decimal::from(30))
+            .low(Decimal::from(5))
+            .close(Decimal::from(25))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+        minute.add_volume(Decimal::from(5), Decimal::from(120), 4);
+        let candle = updated(store.merge(&timeframe, &minute)).candle;
+        assert_eq!(*candle.open(), Decimal::from(10));
+        assert_eq!(*candle.high(), Decimal::from(30));
+        assert_eq!(*candle.low(), Decimal::from(5));
+        assert_eq!(*candle.close(), Decimal::from(25));
+        assert_eq!(*candle.volume(), Decimal::from(10));
+        assert_eq!(*candle.trade_count(), 6);
+        assert_eq!(*candle.vwap(), Some(Decimal::from(20)));
+    }
+
+    #[test]
+    fn stale_and_duplicate_ticks_keep_latest_close() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(5);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+
+        let mut latest = tick(10, at(1, 0));
+        latest.sequence = Some(TickSequence::Ticker(2));
+        updated(store.update(&timeframe, &latest));
+        assert!(matches!(
+            store.update(&timeframe, &latest).unwrap(),
+            Applied::Duplicate
+        ));
+
+        let mut stale = tick(15, at(1, 0));
+        stale.sequence = Some(TickSequence::Ticker(1));
+        let candle = updated(store.update(&timeframe, &stale)).candle;
+        assert_eq!(*candle.high(), Decimal::from(15));
+        assert_eq!(*candle.close(), Decimal::from(10));
+
+        let candle = updated(store.update(&timeframe, &tick(8, at(0, 30)))).candle;
+        assert_eq!(*candle.low(), Decimal::from(8));
+        assert_eq!(*candle.close(), Decimal::from(10));
+        assert_eq!(*candle.last_tick_time(), Some(at(1, 0)));
+        assert_eq!(*candle.last_sequence(), Some(2));
+
+        // Trade ids are not compared with ticker sequences.
+        let mut trade = tick(12, at(1, 0));
+        trade.sequence = Some(TickSequence::Trade(1));
+        let candle = updated(store.update(&timeframe, &trade)).candle;
+        assert_eq!(*candle.close(), Decimal::from(12));
+        assert_eq!(*candle.last_trade_id(), Some(1));
+    }
+
+    #[test]
+    fn trades_delivered_again_are_not_counted() {
+        let mut store = CandleStore::default();
+        let timeframe = Timeframe::Minute(5);
+        let at = |m, s| chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, m, s).unwrap();
+        let trade = |id, price, size, time| Tick {
+            pair: "BTC-USD",
+            price: Decimal::from(price),
+            size: Some(Decimal::from(size)),
+            timestamp: time,
+            sequence: Some(TickSequence::Trade(id)),
+        };
+        let mut apply = |tick: Tick| {
+            if store.accept(&tick) {
+                updated(store.update(&timeframe, &tick));
+            }
+        };
+
+        apply(trade(1, 10, 1, at(0, 10)));
+        apply(trade(2, 12, 2, at(0, 20)));
+        // Older trade sent again, e.g. in the snapshot of a new subscription.
+        apply(trade(1, 10, 1, at(0, 10)));
+        let candle = updated(store.update(&timeframe, &tick(12, at(0, 30)))).candle;
+        assert_eq!(*candle.volume(), Decimal::from(3));
+        assert_eq!(*candle.trade_count(), 2);
+        assert_eq!(*candle.close(), Decimal::from(12));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use types::{BarType, Timeframe};
+
+/// Candle aggregation settings, read from the file at `DATA_PROCESSOR_CONFIG`.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Timeframes aggregated for products without their own configuration.
+    timeframes: Vec<Timeframe>,
+    /// Bar types built for products without their own configuration.
+    #[serde(default)]
+    bars: Vec<BarType>,
+    #[serde(default)]
+    products: HashMap<String, ProductConfig>,
+    #[serde(default)]
+    quality: QualityConfig,
+    #[serde(default)]
+    order_book: OrderBookConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductConfig {
+    timeframes: Vec<Timeframe>,
+    #[serde(default)]
+    bars: Vec<BarType>,
+}
+
+/// Thresholds of the tick validation stage.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Number of recent prices per pair the median is computed on.
+    pub median_window: usize,
+    /// Maximum deviation of a price from the rolling median, in percent.
+    pub max_deviation_percent: Decimal,
+    /// Maximum distance a tick timestamp can be ahead of the wall clock.
+    pub max_clock_skew_ms: i64,
+    /// Maximum distance a tick timestamp can be behind the wall clock.
+    pub max_tick_age_ms: i64,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        return Self {
+            median_window: 50,
+            max_deviation_percent: Decimal::from(5),
+            max_clock_skew_ms: 10_000,
+            max_tick_age_ms: 60_000,
+        };
+    }
+}
+
+/// Sampling of the order books built from the level2 channel.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OrderBookConfig {
+    /// Interval between two samples stored and published per pair.
+    pub interval_ms: u64,
+    /// Width of the depth band on each side of the mid price, in percent.
+    pub depth_percent: Decimal,
+    /// Number of samples the rolling imbalance is averaged over.
+    pub imbalance_window: usize,
+    /// A level of the depth band is a wall when its quantity is at least this
+    /// multiple of the mean quantity of the levels of its side.
+    pub wall_multiple: Decimal,
+}
+
+impl Default for OrderBookConfig {
+    fn default() -> Self {
+        return Self {
+            interval_ms: 1000,
+            depth_percent: Decimal::new(5, 1),
+            imbalance_window: 10,
+            wall_multiple: Decimal::from(5),
+        };
+    }
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let path = std::env::var("DATA_PROCESSOR_CONFIG")
+            .unwrap_or_else(|_| "data-processor.toml".to_owned());
+        let content =
+            std::fs::read_to_string(&path).context(format!("Reading config file {path}"))?;
+
+        return toml::from_str(&content).context(format!("Parsing config file {path}"));
+    }
+
+    pub fn timeframes(&self, pair: &str) -> &[Timeframe] {
+        return self
+            .products
+            .get(pair)
+            .map_or(&self.timeframes, |x| &x.timeframes);
+    }
+
+    pub fn bars(&self, pair: &str) -> &[BarType] {
+        return self.products.get(pair).map_or(&self.bars, |x| &x.bars);
+    }
+
+    pub fn products(&self) -> impl Iterator<Item = &String> {
+        return self.products.keys();
+    }
+
+    pub fn quality(&self) -> &QualityConfig {
+        return &self.quality;
+    }
+
+    pub fn order_book(&self) -> &OrderBookConfig {
+        return &self.order_book;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::{BarType, Timeframe};
+
+    use super::Config;
+
+    #[test]
+    fn default_config_is_valid() {
+        let config: Config = toml::from_str(include_str!("../../data-processor.toml")).unwrap();
+
+        assert!(config.timeframes("ETH-USD").contains(&Timeframe::Hour(4)));
+        assert!(config.timeframes("BTC-USD").contains(&Timeframe::Month(1)));
+        assert_eq!(config.quality().median_window, 50);
+        assert_eq!(config.order_book().imbalance_window, 10);
+        assert!(config
+            .bars("BTC-USD")
+            .contains(&BarType::HeikinAshi(Timeframe::Hour(1))));
+    }
+}
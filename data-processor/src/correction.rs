@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{schema::candles, Candle};
+use tracing::warn;
+use types::Timeframe;
+
+use crate::{
+    candle_store::{upsert_candles, Tick},
+    publisher::publish_candles,
+};
+
+/// Exchange data received for a candle that is already closed.
+pub enum LateData<'a> {
+    Tick(&'a Tick<'a>),
+    Candle(&'a Candle),
+}
+
+/// Applies late data to the stored closed candles, then publishes every
+/// candle that changed on `candle_correction`.
+///
+/// Must run after the candle store was flushed, so the stored candles are the
+/// ones that were published on `candle_close`.
+pub fn correct_closed_candles(
+    late: &[(&Timeframe, LateData)],
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    if late.is_empty() {
+        return Ok(());
+    }
+    let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+    let mut corrected = Vec::new();
+
+    for (timeframe, data) in late.iter() {
+        let (pair, time) = match data {
+            LateData::Tick(tick) => (tick.pair, tick.timestamp),
+            LateData::Candle(candle) => (candle.pair().as_str(), *candle.open_time()),
+        };
+        let (open_time, _) = timeframe.open_and_size(&time)?;
+        let stored: Option<Candle> = candles::table
+            .filter(
+                candles::pair
+                    .eq(pair)
+                    .and(candles::timeframe.eq(timeframe.to_string()))
+                    .and(candles::open_time.eq(open_time)),
+            )
+            .select(Candle::as_select())
+            .first(pg_conn)
+            .optional()
+            .context(format!(
+                "Loading closed {timeframe} candle {pair} at {open_time}"
+            ))?;
+        let mut candle = match stored {
+            Some(candle) => candle,
+            None => {
+                warn!("No stored {timeframe} candle to correct: {pair} at {open_time}");
+                continue;
+            }
+        };
+
+        let changed = match data {
+            LateData::Tick(tick) => {
+                candle.apply_tick(tick.price, tick.size, tick.timestamp, tick.sequence)
+            }
+            LateData::Candle(other) => {
+                candle.merge(other);
+                true
+            }
+        };
+        if !changed {
+            continue;
+        }
+        warn!("Correcting closed {timeframe} candle: {pair} at {open_time}");
+        upsert_candles(&[&candle], pg_conn)?;
+        corrected.push(candle);
+    }
+
+    let redis_conn = &mut redis_pool
+        .get()
+        .context("Getting connection from redis_pool")?;
+    return publish_candles(
+        redis_conn,
+        &corrected,
+        Cow::Borrowed("candle_correction"),
+        is_backtest,
+    );
+}
@@ -0,0 +1,51 @@
+use anyhow::Context;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use models::Candle;
+
+use crate::{
+    candle_store::{Applied, CandleStore},
+    config::Config,
+    correction::{correct_closed_candles, LateData},
+    publisher::publish_updates,
+};
+
+/// Aggregates a 1 minute candle fetched from the exchange, with its volume,
+/// into the candles of every configured timeframe.
+///
+/// Heikin-Ashi bars follow the candles they are built from, bars built from
+/// trades are not built from exchange candles.
+pub fn handle_exchange_candle(
+    payload: String,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    candle_store: &mut CandleStore,
+    config: &Config,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    let data: Candle = serde_json::from_str(&payload).context("Parsing redis message to Candle")?;
+    let mut closed_candles = Vec::new();
+    let mut updated_candles = Vec::new();
+    let mut late = Vec::new();
+
+    for timeframe in config.timeframes(data.pair()).iter() {
+        match candle_store.merge(timeframe, &data)? {
+            Applied::Updated(update) => {
+                closed_candles.extend(update.closed);
+                updated_candles.push(*update.candle);
+            }
+            Applied::Duplicate => (),
+            Applied::Closed => late.push((timeframe, LateData::Candle(&data))),
+        }
+    }
+
+    publish_updates(
+        &closed_candles,
+        &updated_candles,
+        candle_store,
+        config,
+        redis_pool.clone(),
+        pg_pool.clone(),
+        is_backtest,
+    )?;
+    return correct_closed_candles(&late, redis_pool, pg_pool, is_backtest);
+}
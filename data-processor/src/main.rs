@@ -1,10 +1,28 @@
+mod backfill;
+mod bars;
+mod candle_store;
+mod config;
+mod correction;
+mod exchange_candle;
+mod market_trades;
+mod order_book;
+mod publisher;
+mod quality;
+mod rebuild;
+mod scheduler;
 mod ticker;
 
-use std::future;
+use std::{collections::HashSet, sync::Arc};
 
 use anyhow::{bail, Context};
+use candle_store::CandleStore;
+use coinbase_advanced_api::rest::client::RestClient;
+use config::Config;
 use diesel::{r2d2::ConnectionManager, PgConnection};
-use tracing::error;
+use order_book::OrderBooks;
+use quality::QualityFilter;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
 
 async fn handle_redis_message(msg: redis::Msg, state: AppState) -> anyhow::Result<()> {
     let channel: String = msg
@@ -13,9 +31,81 @@ async fn handle_redis_message(msg: redis::Msg, state: AppState) -> anyhow::Resul
     let payload: String = msg.get_payload()?;
 
     match channel.as_str() {
-        "ticker" => ticker::handle_ticker(payload, state.redis_pool, state.pg_pool, false)?,
-        "backtest-ticker" => {
-            ticker::handle_ticker(payload, state.redis_pool, state.pg_pool_backtest, true)?
+        "ticker" => ticker::handle_ticker(
+            payload,
+            state.redis_pool,
+            state.pg_pool,
+            &mut *state.candle_store.lock().await,
+            &mut *state.quality_filter.lock().await,
+            &state.config,
+            false,
+        )?,
+        "backtest-ticker" => ticker::handle_ticker(
+            payload,
+            state.redis_pool,
+            state.pg_pool_backtest,
+            &mut *state.candle_store_backtest.lock().await,
+            &mut *state.quality_filter_backtest.lock().await,
+            &state.config,
+            true,
+        )?,
+        "market_trades" => market_trades::handle_market_trades(
+            payload,
+            state.redis_pool,
+            state.pg_pool,
+            &mut *state.candle_store.lock().await,
+            &mut *state.quality_filter.lock().await,
+            &state.config,
+            false,
+        )?,
+        "backtest-exchange_candle" => exchange_candle::handle_exchange_candle(
+            payload,
+            state.redis_pool,
+            state.pg_pool_backtest,
+            &mut *state.candle_store_backtest.lock().await,
+            &state.config,
+            true,
+        )?,
+        "backtest-reset" => {
+            state.candle_store_backtest.lock().await.clear();
+            state.quality_filter_backtest.lock().await.clear();
+            // Backtests replay exchange candles, without the trades these bars are built from.
+            let trade_bars: Vec<String> = state
+                .config
+                .bars(&payload)
+                .iter()
+                .filter(|x| x.from_trades())
+                .map(|x| x.to_string())
+                .collect();
+            if !trade_bars.is_empty() {
+                warn!(
+                    "Not building {} bars of {payload} in backtest",
+                    trade_bars.join(", ")
+                );
+            }
+        }
+        // Backfilled without blocking the ticks received in the meantime, once
+        // at a time per pair.
+        "market_data_gap" => {
+            if !state.backfilling.lock().await.insert(payload.clone()) {
+                return Ok(());
+            }
+            tokio::spawn(async move {
+                let res = backfill::backfill_pair(
+                    &payload,
+                    &state.rest_client,
+                    &state.candle_store,
+                    &state.config,
+                    state.redis_pool,
+                    state.pg_pool,
+                )
+                .await
+                .context(format!("Backfilling {payload} after market data gap"));
+                state.backfilling.lock().await.remove(&payload);
+                if let Err(err) = res {
+                    error!("{err:#}");
+                }
+            });
         }
         _ => bail!("No handler for redis channel {channel}"),
     };
@@ -48,10 +138,56 @@ fn init_redis_pool() -> anyhow::Result<r2d2::Pool<redis::Client>> {
         .context("Creating RedisPool");
 }
 
+fn init_candle_store(
+    pg_pool: &r2d2::Pool<ConnectionManager<PgConnection>>,
+    config: &Config,
+) -> anyhow::Result<Arc<Mutex<CandleStore>>> {
+    let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+    let mut candle_store =
+        CandleStore::load(pg_conn).context("Loading candle_store from database")?;
+    rebuild::rebuild_missing_timeframes(config, &mut candle_store, pg_conn)
+        .context("Rebuilding missing timeframes")?;
+
+    return Ok(Arc::new(Mutex::new(candle_store)));
+}
+
+/// Periodically writes the candles updated in memory to the database.
+async fn flush_candle_store(
+    candle_store: Arc<Mutex<CandleStore>>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let mut candle_store = candle_store.lock().await;
+        if !candle_store.has_pending_writes() {
+            continue;
+        }
+        let res = pg_pool
+            .get()
+            .context("Getting connection from pg_pool")
+            .and_then(|mut pg_conn| candle_store.flush(&mut pg_conn));
+        if let Err(err) = res {
+            error!("{err:#}");
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct AppState {
     pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
     pg_pool_backtest: r2d2::Pool<ConnectionManager<PgConnection>>,
     redis_pool: r2d2::Pool<redis::Client>,
+    candle_store: Arc<Mutex<CandleStore>>,
+    candle_store_backtest: Arc<Mutex<CandleStore>>,
+    quality_filter: Arc<Mutex<QualityFilter>>,
+    quality_filter_backtest: Arc<Mutex<QualityFilter>>,
+    order_books: Arc<Mutex<OrderBooks>>,
+    /// Pairs being backfilled after a market data gap.
+    backfilling: Arc<Mutex<HashSet<String>>>,
+    config: Arc<Config>,
+    rest_client: Arc<RestClient>,
 }
 
 #[tokio::main]
@@ -61,31 +197,95 @@ async fn main() -> anyhow::Result<()> {
     let pg_pool = init_pg_pool(false)?;
     let pg_pool_backtest = init_pg_pool(true)?;
     let redis_pool = init_redis_pool()?;
-    let mut redis_sub_conn = redis_pool
+    let flush_interval = std::env::var("CANDLE_FLUSH_INTERVAL_MS")
+        .map_or(Ok(1000), |x| x.parse::<u64>())
+        .context("CANDLE_FLUSH_INTERVAL_MS from .env file")?;
+    let close_grace = std::env::var("CANDLE_CLOSE_GRACE_MS")
+        .map_or(Ok(2000), |x| x.parse::<i64>())
+        .context("CANDLE_CLOSE_GRACE_MS from .env file")?;
+    let config = Config::load()?;
+    let api_key = std::env::var("CB_API_KEY").context("CB_API_KEY from .env file")?;
+    let private_key = std::env::var("CB_PRIVATE_KEY").context("CB_PRIVATE_KEY from .env file")?;
+    let state = AppState {
+        candle_store: init_candle_store(&pg_pool, &config)?,
+        candle_store_backtest: init_candle_store(&pg_pool_backtest, &config)?,
+        quality_filter: Arc::new(Mutex::new(QualityFilter::default())),
+        quality_filter_backtest: Arc::new(Mutex::new(QualityFilter::default())),
+        order_books: Arc::new(Mutex::new(OrderBooks::default())),
+        backfilling: Arc::new(Mutex::new(HashSet::new())),
+        config: Arc::new(config),
+        rest_client: Arc::new(RestClient::new(&api_key, &private_key)?),
+        pg_pool,
+        pg_pool_backtest,
+        redis_pool,
+    };
+
+    // Candles missed while the data-processor was down are filled before live ticks are applied.
+    backfill::backfill_all(
+        &state.rest_client,
+        &state.candle_store,
+        &state.config,
+        state.redis_pool.clone(),
+        state.pg_pool.clone(),
+    )
+    .await;
+
+    for (candle_store, pg_pool) in [
+        (state.candle_store.clone(), state.pg_pool.clone()),
+        (
+            state.candle_store_backtest.clone(),
+            state.pg_pool_backtest.clone(),
+        ),
+    ] {
+        tokio::spawn(flush_candle_store(
+            candle_store,
+            pg_pool,
+            std::time::Duration::from_millis(flush_interval),
+        ));
+    }
+
+    // Backtest ticks carry historical timestamps, so only live candles are closed on a timer.
+    tokio::spawn(scheduler::run_close_scheduler(
+        state.candle_store.clone(),
+        state.config.clone(),
+        state.redis_pool.clone(),
+        state.pg_pool.clone(),
+        chrono::Duration::milliseconds(close_grace),
+        false,
+    ));
+
+    // Level2 data is only collected live.
+    let (order_books, redis_pool) = (state.order_books.clone(), state.redis_pool.clone());
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = order_book::listen_level2(order_books, redis_pool) {
+            error!("{err:#}");
+        }
+    });
+    tokio::spawn(order_book::run_order_book_sampler(
+        state.order_books.clone(),
+        state.config.clone(),
+        state.redis_pool.clone(),
+        state.pg_pool.clone(),
+    ));
+
+    let mut redis_sub_conn = state
+        .redis_pool
         .get()
         .context("Get redis_sub_conn from redis_pool")?;
     let mut pubsub = redis_sub_conn.as_pubsub();
 
     pubsub.subscribe("ticker")?;
     pubsub.subscribe("backtest-ticker")?;
+    pubsub.subscribe("market_trades")?;
+    pubsub.subscribe("backtest-exchange_candle")?;
+    pubsub.subscribe("backtest-reset")?;
+    pubsub.subscribe("market_data_gap")?;
     loop {
         let msg = pubsub.get_message()?;
-        let pg_pool = pg_pool.clone();
-        let pg_pool_backtest = pg_pool_backtest.clone();
-        let redis_pool = redis_pool.clone();
-        let state = AppState {
-            pg_pool,
-            pg_pool_backtest,
-            redis_pool,
-        };
 
-        tokio::spawn(async {
-            let res = handle_redis_message(msg, state).await;
-
-            if let Err(err) = res {
-                error!("{err:#}");
-            }
-            future::ready(())
-        });
+        // Ticks are handled one at a time so candles are built in the order they were received.
+        if let Err(err) = handle_redis_message(msg, state.clone()).await {
+            error!("{err:#}");
+        }
     }
 }
@@ -0,0 +1,48 @@
+use anyhow::Context;
+use coinbase_advanced_api::ws::channel::{market_trades::MarketTradesEvent, Response};
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use models::candle::TickSequence;
+
+use crate::{
+    candle_store::{CandleStore, Tick},
+    config::Config,
+    quality::QualityFilter,
+    ticker::apply_ticks,
+};
+
+pub fn handle_market_trades(
+    payload: String,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    candle_store: &mut CandleStore,
+    quality_filter: &mut QualityFilter,
+    config: &Config,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    let data: Response<MarketTradesEvent> = serde_json::from_str(&payload)
+        .context("Parsing redis message to Response<MarketTradesEvent>")?;
+    let mut ticks: Vec<Tick> = data
+        .events()
+        .iter()
+        .flat_map(|event| event.trades().iter())
+        .map(|trade| Tick {
+            pair: trade.product_id(),
+            price: trade.price().to_owned(),
+            size: Some(trade.size().to_owned()),
+            timestamp: trade.time().to_owned(),
+            sequence: trade.trade_id().parse().ok().map(TickSequence::Trade),
+        })
+        .collect();
+    // Coinbase sends the most recent trades first.
+    ticks.sort_by_key(|x| (x.timestamp, x.sequence));
+
+    return apply_ticks(
+        &ticks,
+        redis_pool,
+        pg_pool,
+        candle_store,
+        quality_filter,
+        config,
+        is_backtest,
+    );
+}
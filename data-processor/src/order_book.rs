@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use coinbase_advanced_api::ws::channel::{
+    level2::{Level2Event, Level2Side},
+    EventType, Response,
+};
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    order_book_metric::{OrderBookMetric, OrderBookMetricBuilder},
+    schema::order_book_metrics,
+};
+use redis::Commands;
+use rust_decimal::Decimal;
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::config::{Config, OrderBookConfig};
+
+/// Books without updates for this long are not sampled, their feed being down.
+const STALE_AFTER: chrono::Duration = chrono::Duration::seconds(30);
+
+/// Quantity resting at each price level of a pair.
+#[derive(Default)]
+struct OrderBook {
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    imbalances: VecDeque<Decimal>,
+    updated_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl OrderBook {
+    fn apply(&mut self, event: &Level2Event, timestamp: chrono::DateTime<chrono::Utc>) {
+        if let EventType::Snapshot = event.r#type {
+            self.bids.clear();
+            self.asks.clear();
+        }
+        for update in event.updates.iter() {
+            let levels = match update.side {
+                Level2Side::Bid => &mut self.bids,
+                Level2Side::Offer => &mut self.asks,
+            };
+            if update.new_quantity.is_zero() {
+                levels.remove(&update.price_level);
+            } else {
+                levels.insert(update.price_level, update.new_quantity);
+            }
+        }
+        self.updated_time = Some(timestamp);
+    }
+
+    /// Metrics of the book at `time`, None while one of its sides is empty.
+    fn sample(
+        &mut self,
+        pair: &str,
+        time: chrono::DateTime<chrono::Utc>,
+        config: &OrderBookConfig,
+    ) -> anyhow::Result<Option<OrderBookMetric>> {
+        let ((best_bid, bid_size), (best_ask, ask_size)) =
+            match (self.bids.last_key_value(), self.asks.first_key_value()) {
+                (Some((bid, bid_size)), Some((ask, ask_size))) => {
+                    ((*bid, *bid_size), (*ask, *ask_size))
+                }
+                _ => return Ok(None),
+            };
+        let mid = (best_bid + best_ask) / Decimal::TWO;
+        let band = mid * config.depth_percent / Decimal::ONE_HUNDRED;
+        let bids: Vec<(Decimal, Decimal)> = self
+            .bids
+            .range(mid - band..)
+            .map(|(price, quantity)| (*price, *quantity))
+            .collect();
+        let asks: Vec<(Decimal, Decimal)> = self
+            .asks
+            .range(..=mid + band)
+            .map(|(price, quantity)| (*price, *quantity))
+            .collect();
+        let bid_depth: Decimal = bids.iter().map(|x| x.1).sum();
+        let ask_depth: Decimal = asks.iter().map(|x| x.1).sum();
+
+        let imbalance = match bid_depth + ask_depth {
+            total if total.is_zero() => Decimal::ZERO,
+            total => (bid_depth - ask_depth) / total,
+        };
+        self.imbalances.push_back(imbalance);
+        while self.imbalances.len() > config.imbalance_window.max(1) {
+            self.imbalances.pop_front();
+        }
+        let rolling_imbalance =
+            self.imbalances.iter().sum::<Decimal>() / Decimal::from(self.imbalances.len());
+
+        let bid_wall = find_wall(&bids, config.wall_multiple);
+        let ask_wall = find_wall(&asks, config.wall_multiple);
+        let metric = OrderBookMetricBuilder::default()
+            .pair(pair.to_owned())
+            .time(time)
+            .best_bid(best_bid)
+            .best_ask(best_ask)
+            .spread(best_ask - best_bid)
+            .bid_size(bid_size)
+            .ask_size(ask_size)
+            .bid_depth(bid_depth)
+            .ask_depth(ask_depth)
+            .imbalance(imbalance)
+            .rolling_imbalance(rolling_imbalance)
+            .bid_wall_price(bid_wall.map(|x| x.0))
+            .bid_wall_size(bid_wall.map(|x| x.1))
+            .ask_wall_price(ask_wall.map(|x| x.0))
+            .ask_wall_size(ask_wall.map(|x| x.1))
+            .build()?;
+        return Ok(Some(metric));
+    }
+}
+
+/// Largest level of `levels` when its quantity is at least `wall_multiple`
+/// times their mean quantity.
+fn find_wall(levels: &[(Decimal, Decimal)], wall_multiple: Decimal) -> Option<(Decimal, Decimal)> {
+    let largest = levels.iter().max_by_key(|x| x.1)?;
+    let mean = levels.iter().map(|x| x.1).sum::<Decimal>() / Decimal::from(levels.len());
+    if largest.1 < mean * wall_multiple {
+        return None;
+    }
+    return Some(*largest);
+}
+
+/// Order books of every pair received on the `l2_data` channel.
+#[derive(Default)]
+pub struct OrderBooks {
+    books: HashMap<String, OrderBook>,
+}
+
+impl OrderBooks {
+    /// Applies a level2 message, a snapshot replacing the book of its pair.
+    pub fn apply(&mut self, response: &Response<Level2Event>) {
+        for event in response.events.iter() {
+            self.books
+                .entry(event.product_id.to_owned())
+                .or_default()
+                .apply(event, response.timestamp);
+        }
+    }
+
+    /// Metrics of the books updated within `STALE_AFTER` of `time`.
+    pub fn sample(
+        &mut self,
+        time: chrono::DateTime<chrono::Utc>,
+        config: &OrderBookConfig,
+    ) -> anyhow::Result<Vec<OrderBookMetric>> {
+        let mut metrics = Vec::new();
+        for (pair, book) in self.books.iter_mut() {
+            if book.updated_time.is_none_or(|x| time - x > STALE_AFTER) {
+                continue;
+            }
+            if let Some(metric) = book
+                .sample(pair, time, config)
+                .context(format!("Sampling {pair} order book"))?
+            {
+                metrics.push(metric);
+            }
+        }
+        return Ok(metrics);
+    }
+}
+
+pub fn handle_level2(payload: String, order_books: &mut OrderBooks) -> anyhow::Result<()> {
+    let data: Response<Level2Event> =
+        serde_json::from_str(&payload).context("Parsing redis message to Response<Level2Event>")?;
+    order_books.apply(&data);
+    return Ok(());
+}
+
+/// Applies the updates received on `l2_data` to `order_books`, on a redis
+/// connection of its own so their volume does not delay ticks.
+pub fn listen_level2(
+    order_books: Arc<Mutex<OrderBooks>>,
+    redis_pool: r2d2::Pool<redis::Client>,
+) -> anyhow::Result<()> {
+    let mut redis_sub_conn = redis_pool
+        .get()
+        .context("Get level2 redis_sub_conn from redis_pool")?;
+    let mut pubsub = redis_sub_conn.as_pubsub();
+    pubsub.subscribe("l2_data")?;
+    loop {
+        let msg = pubsub.get_message()?;
+        let res = msg
+            .get_payload()
+            .context("payload from redis pubsub message")
+            .and_then(|payload| handle_level2(payload, &mut order_books.blocking_lock()));
+        if let Err(err) = res {
+            error!("{err:#}");
+        }
+    }
+}
+
+/// Stores the metrics of the order books in `order_book_metrics` and publishes
+/// them on `order_book` every `interval_ms` of the order book configuration.
+pub async fn run_order_book_sampler(
+    order_books: Arc<Mutex<OrderBooks>>,
+    config: Arc<Config>,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(
+        config.order_book().interval_ms,
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let metrics = order_books
+            .lock()
+            .await
+            .sample(chrono::Utc::now(), config.order_book());
+        let res = metrics.and_then(|metrics| save_metrics(&metrics, &redis_pool, &pg_pool));
+        if let Err(err) = res {
+            error!("{err:#}");
+        }
+    }
+}
+
+fn save_metrics(
+    metrics: &[OrderBookMetric],
+    redis_pool: &r2d2::Pool<redis::Client>,
+    pg_pool: &r2d2::Pool<ConnectionManager<PgConnection>>,
+) -> anyhow::Result<()> {
+    if metrics.is_empty() {
+        return Ok(());
+    }
+    let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+    diesel::insert_into(order_book_metrics::table)
+        .values(metrics)
+        .on_conflict_do_nothing()
+        .execute(pg_conn)
+        .context("Inserting order_book_metrics")?;
+
+    let redis_conn = &mut redis_pool
+        .get()
+        .context("Getting connection from redis_pool")?;
+    for metric in metrics.iter() {
+        let _: () = redis_conn
+            .publish(
+                "order_book",
+                serde_json::to_string(metric)
+                    .context("Stringify order book metric for publishing on redis order_book")?,
+            )
+            .context("Publishing to redis order_book channel")?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use coinbase_advanced_api::ws::channel::{level2::Level2Event, Response};
+    use rust_decimal::Decimal;
+
+    use super::OrderBooks;
+    use crate::config::OrderBookConfig;
+
+    fn message(r#type: &str, updates: &[(&str, &str, &str)]) -> Response<Level2Event> {
+        let updates: Vec<serde_json::Value> = updates
+            .iter()
+            .map(|(side, price, quantity)| {
+                serde_json::json!({
+                    "side": side,
+                    "event_time": "2024-01-01T00:00:00Z",
+                    "price_level": price,
+                    "new_quantity": quantity,
+                })
+            })
+            .collect();
+        let message = serde_json::json!({
+            "channel": "l2_data",
+            "client_id": "",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "sequence_num": 0,
+            "events": [{ "type": r#type, "product_id": "BTC-USD", "updates": updates }],
+        });
+        return serde_json::from_value(message).unwrap();
+    }
+
+    #[test]
+    fn samples_spread_depth_imbalance_and_walls() {
+        let config = OrderBookConfig {
+            depth_percent: Decimal::TWO,
+            imbalance_window: 2,
+            wall_multiple: Decimal::TWO,
+            ..Default::default()
+        };
+        let time = chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 1).unwrap();
+        let mut order_books = OrderBooks::default();
+        order_books.apply(&message(
+            "snapshot",
+            &[
+                ("bid", "99", "1"),
+                ("bid", "98.5", "1"),
+                ("bid", "98", "7"),
+                // Outside of the depth band.
+                ("bid", "90", "100"),
+                ("offer", "101", "2"),
+                ("offer", "101.5", "2"),
+            ],
+        ));
+
+        let metric = order_books.sample(time, &config).unwrap().remove(0);
+        assert_eq!(*metric.spread(), Decimal::TWO);
+        assert_eq!(*metric.bid_size(), Decimal::ONE);
+        assert_eq!(*metric.bid_depth(), Decimal::from(9));
+        assert_eq!(*metric.ask_depth(), Decimal::from(4));
+        assert_eq!(*metric.imbalance(), Decimal::from(5) / Decimal::from(13));
+        assert_eq!(*metric.bid_wall_price(), Some(Decimal::from(98)));
+        assert_eq!(*metric.ask_wall_price(), None);
+
+        order_books.apply(&message(
+            "update",
+            &[("bid", "98", "0"), ("bid", "98.5", "3")],
+        ));
+        let metric = order_books.sample(time, &config).unwrap().remove(0);
+        assert_eq!(*metric.imbalance(), Decimal::ZERO);
+        assert_eq!(
+            *metric.rolling_imbalance(),
+            Decimal::from(5) / Decimal::from(13) / Decimal::TWO
+        );
+        assert_eq!(*metric.bid_wall_price(), None);
+
+        // A new snapshot replaces the book, stale books are not sampled.
+        order_books.apply(&message("snapshot", &[("bid", "99", "1")]));
+        assert!(order_books.sample(time, &config).unwrap().is_empty());
+        let later = time + chrono::Duration::minutes(1);
+        order_books.apply(&message("update", &[("offer", "100", "1")]));
+        assert!(order_books.sample(later, &config).unwrap().is_empty());
+    }
+}
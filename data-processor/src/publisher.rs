@@ -0,0 +1,71 @@
+use std::borrow::Cow;
+
+use anyhow::Context;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use models::Candle;
+use redis::Commands;
+
+use crate::{candle_store::CandleStore, config::Config};
+
+pub fn publish_candles(
+    redis_conn: &mut redis::Connection,
+    candles: &[Candle],
+    channel: Cow<'static, str>,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    let channel = if is_backtest {
+        Cow::Owned(format!("backtest-{channel}"))
+    } else {
+        channel
+    };
+    for candle in candles.iter() {
+        let _: () = redis_conn
+            .publish(
+                channel.to_string(),
+                serde_json::to_string(candle).context(format!(
+                    "Stringify candle for publishing on redis {channel}"
+                ))?,
+            )
+            .context(format!("Publishing to redis {channel} channel"))?;
+    }
+    return Ok(());
+}
+
+/// Publishes closed candles, followed by their Heikin-Ashi candles, on
+/// `candle_close` and updated candles on `candle`.
+pub fn publish_updates(
+    closed_candles: &[Candle],
+    updated_candles: &[Candle],
+    candle_store: &mut CandleStore,
+    config: &Config,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    let mut closed_candles = closed_candles.to_vec();
+    closed_candles.extend(candle_store.heikin_ashi(&closed_candles, config)?);
+    if !closed_candles.is_empty() {
+        // Indicators read previous candles from the database, so closed candles
+        // must be persisted before their close is published.
+        let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+        candle_store
+            .flush(pg_conn)
+            .context("Flushing candle_store on candle close")?;
+    }
+    let redis_conn = &mut redis_pool
+        .get()
+        .context("Getting connection from redis_pool")?;
+    publish_candles(
+        redis_conn,
+        &closed_candles,
+        Cow::Borrowed("candle_close"),
+        is_backtest,
+    )?;
+    publish_candles(
+        redis_conn,
+        updated_candles,
+        Cow::Borrowed("candle"),
+        is_backtest,
+    )?;
+    return Ok(());
+}
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Context;
+use redis::Commands;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{candle_store::Tick, config::QualityConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    NonPositivePrice,
+    PriceDeviation,
+    ClockSkew,
+    Stale,
+}
+
+/// A tick rejected by the quality filter, published on `data_quality`.
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    pub pair: String,
+    pub price: Decimal,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub reason: RejectReason,
+    pub median: Option<Decimal>,
+    /// Number of ticks rejected for the same pair and reason so far.
+    pub count: u64,
+}
+
+/// Rejects ticks with a zero or negative price, a price too far from the
+/// rolling median of the pair, or a timestamp too far ahead of or behind the
+/// wall clock. Past timestamps have their own bound, snapshots sent on
+/// subscribe being older than live ticks.
+///
+/// Prices rejected for their deviation still enter the median window, so a
+/// single bad print is dropped while a genuine move is accepted once it lasts.
+#[derive(Default)]
+pub struct QualityFilter {
+    prices: HashMap<String, VecDeque<Decimal>>,
+    counts: HashMap<(String, RejectReason), u64>,
+}
+
+impl QualityFilter {
+    pub fn clear(&mut self) {
+        self.prices.clear();
+        self.counts.clear();
+    }
+
+    /// Checks `tick` against `config`. The clock skew is only checked when `now`
+    /// is given, as backtest ticks carry historical timestamps.
+    pub fn check(
+        &mut self,
+        tick: &Tick,
+        config: &QualityConfig,
+        now: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Option<Rejection> {
+        if tick.price <= Decimal::ZERO {
+            return Some(self.reject(tick, RejectReason::NonPositivePrice, None));
+        }
+        if now
+            .is_some_and(|now| (tick.timestamp - now).num_milliseconds() > config.max_clock_skew_ms)
+        {
+            return Some(self.reject(tick, RejectReason::ClockSkew, None));
+        }
+        if now.is_some_and(|now| (now - tick.timestamp).num_milliseconds() > config.max_tick_age_ms)
+        {
+            return Some(self.reject(tick, RejectReason::Stale, None));
+        }
+
+        let prices = self.prices.entry(tick.pair.to_owned()).or_default();
+        let median = median(prices, config.median_window / 2);
+        prices.push_back(tick.price);
+        while prices.len() > config.median_window {
+            prices.pop_front();
+        }
+        if let Some(median) = median {
+            let deviation = ((tick.price - median) / median).abs() * Decimal::ONE_HUNDRED;
+            if deviation > config.max_deviation_percent {
+                return Some(self.reject(tick, RejectReason::PriceDeviation, Some(median)));
+            }
+        }
+        return None;
+    }
+
+    fn reject(&mut self, tick: &Tick, reason: RejectReason, median: Option<Decimal>) -> Rejection {
+        let count = self
+            .counts
+            .entry((tick.pair.to_owned(), reason))
+            .or_default();
+        *count += 1;
+
+        return Rejection {
+            pair: tick.pair.to_owned(),
+            price: tick.price,
+            timestamp: tick.timestamp,
+            reason,
+            median,
+            count: *count,
+        };
+    }
+}
+
+/// Median of `prices`, or `None` while fewer than `min_samples` are known.
+fn median(prices: &VecDeque<Decimal>, min_samples: usize) -> Option<Decimal> {
+    if prices.is_empty() || prices.len() < min_samples {
+        return None;
+    }
+    let mut sorted: Vec<Decimal> = prices.iter().copied().collect();
+    sorted.sort();
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        return Some((sorted[middle - 1] + sorted[middle]) / Decimal::TWO);
+    }
+    return Some(sorted[middle]);
+}
+
+/// Drops the ticks rejected by `quality_filter`, logging and publishing each
+/// rejection on `data_quality`.
+pub fn filter_ticks<'a>(
+    ticks: &'a [Tick<'a>],
+    quality_filter: &mut QualityFilter,
+    config: &QualityConfig,
+    redis_pool: &r2d2::Pool<redis::Client>,
+    is_backtest: bool,
+) -> anyhow::Result<Vec<&'a Tick<'a>>> {
+    let now = (!is_backtest).then(chrono::Utc::now);
+    let mut accepted = Vec::with_capacity(ticks.len());
+    let mut rejections = Vec::new();
+
+    for tick in ticks.iter() {
+        match quality_filter.check(tick, config, now) {
+            Some(rejection) => {
+                warn!(
+                    "Rejecting {} tick at {} price={}: {:?} ({} so far)",
+                    rejection.pair,
+                    rejection.timestamp,
+                    rejection.price,
+                    rejection.reason,
+                    rejection.count
+                );
+                rejections.push(rejection);
+            }
+            None => accepted.push(tick),
+        }
+    }
+    if rejections.is_empty() {
+        return Ok(accepted);
+    }
+
+    let channel = match is_backtest {
+        false => "data_quality",
+        true => "backtest-data_quality",
+    };
+    let redis_conn = &mut redis_pool
+        .get()
+        .context("Getting connection from redis_pool")?;
+    for rejection in rejections.iter() {
+        let _: () = redis_conn
+            .publish(
+                channel,
+                serde_json::to_string(rejection).context(format!(
+                    "Stringify rejection for publishing on redis {channel}"
+                ))?,
+            )
+            .context(format!("Publishing to redis {channel} channel"))?;
+    }
+    return Ok(accepted);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+
+    use super::{QualityFilter, RejectReason};
+    use crate::{candle_store::Tick, config::QualityConfig};
+
+    #[test]
+    fn rejects_bad_prints() {
+        let config = QualityConfig {
+            median_window: 4,
+            ..Default::default()
+        };
+        let now = chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let tick = |price: i64, seconds: i64| Tick {
+            pair: "BTC-USD",
+            price: Decimal::from(price),
+            size: None,
+            timestamp: now + chrono::Duration::seconds(seconds),
+            sequence: None,
+        };
+        let mut filter = QualityFilter::default();
+
+        assert!(filter.check(&tick(100, 0), &config, Some(now)).is_none());
+        assert!(filter.check(&tick(101, 0), &config, Some(now)).is_none());
+        let rejection = filter.check(&tick(150, 0), &config, Some(now)).unwrap();
+        assert_eq!(rejection.reason, RejectReason::PriceDeviation);
+        assert_eq!(
+            filter
+                .check(&tick(0, 0), &config, Some(now))
+                .unwrap()
+                .reason,
+            RejectReason::NonPositivePrice
+        );
+        assert_eq!(
+            filter
+                .check(&tick(100, 60), &config, Some(now))
+                .unwrap()
+                .reason,
+            RejectReason::ClockSkew
+        );
+        assert!(filter.check(&tick(100, 60), &config, None).is_none());
+
+        // A lasting move becomes the median and is accepted.
+        filter.check(&tick(150, 0), &config, Some(now));
+        filter.check(&tick(150, 0), &config, Some(now));
+        assert!(filter.check(&tick(150, 0), &config, Some(now)).is_none());
+
+        // Snapshot ticks can be older than the clock skew.
+        assert!(filter.check(&tick(150, -30), &config, Some(now)).is_none());
+        assert_eq!(
+            filter
+                .check(&tick(150, -3600), &config, Some(now))
+                .unwrap()
+                .reason,
+            RejectReason::Stale
+        );
+    }
+}
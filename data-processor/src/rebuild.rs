@@ -0,0 +1,91 @@
+use std::{collections::HashSet, str::FromStr};
+
+use chrono::TimeZone;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{schema::candles, Candle};
+use tracing::{info, warn};
+use types::Timeframe;
+
+use crate::{candle_store::CandleStore, config::Config};
+
+const REBUILD_BATCH_SIZE: i64 = 10_000;
+
+/// Builds the history of every configured timeframe that has no stored candle
+/// yet, from the finest timeframe stored for the same pair that divides it.
+pub fn rebuild_missing_timeframes(
+    config: &Config,
+    candle_store: &mut CandleStore,
+    pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+) -> anyhow::Result<()> {
+    let pairs: HashSet<String> = candle_store
+        .pairs()
+        .chain(config.products().cloned())
+        .collect();
+
+    for pair in pairs.iter() {
+        let stored: Vec<Timeframe> = candle_store
+            .timeframes(pair)
+            .filter_map(|x| Timeframe::from_str(x).ok())
+            .collect();
+        if stored.is_empty() {
+            continue;
+        }
+        for timeframe in config.timeframes(pair) {
+            if stored.contains(timeframe) {
+                continue;
+            }
+            // Candles straddling the boundaries of the timeframe would be merged
+            // into the wrong candle.
+            let source = match stored
+                .iter()
+                .filter(|x| x.divides(timeframe))
+                .min_by_key(|x| x.nominal_duration())
+            {
+                Some(source) => source,
+                None => {
+                    warn!(
+                        "Cannot rebuild {pair} {timeframe} candles, no stored timeframe divides it"
+                    );
+                    continue;
+                }
+            };
+            info!("Rebuilding {pair} {timeframe} candles from {source} candles");
+            rebuild_timeframe(pair, source, timeframe, candle_store, pg_conn)?;
+        }
+    }
+    return Ok(());
+}
+
+fn rebuild_timeframe(
+    pair: &str,
+    source: &Timeframe,
+    timeframe: &Timeframe,
+    candle_store: &mut CandleStore,
+    pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+) -> anyhow::Result<()> {
+    let mut after = chrono::Utc.timestamp_opt(0, 0).unwrap();
+
+    loop {
+        let source_candles: Vec<Candle> = candles::table
+            .filter(
+                candles::pair
+                    .eq(pair)
+                    .and(candles::timeframe.eq(source.to_string()))
+                    .and(candles::open_time.gt(after)),
+            )
+            .select(Candle::as_select())
+            .order(candles::open_time.asc())
+            .limit(REBUILD_BATCH_SIZE)
+            .get_results(pg_conn)?;
+        let last = match source_candles.last() {
+            Some(last) => last.open_time().to_owned(),
+            None => break,
+        };
+        for candle in source_candles.iter() {
+            candle_store.merge(timeframe, candle)?;
+        }
+        candle_store.flush(pg_conn)?;
+        after = last;
+    }
+    return Ok(());
+}
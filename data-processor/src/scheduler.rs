@@ -0,0 +1,79 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Context;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::{candle_store::CandleStore, config::Config, publisher::publish_candles};
+
+/// Upper bound on how long the scheduler sleeps, so candles opened while it waits
+/// are picked up.
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Emits candle closes as soon as their period ends instead of waiting for the
+/// first tick of the next period.
+///
+/// Closes are emitted `grace` after the end of the period so late ticks can still
+/// be applied to the candle.
+pub async fn run_close_scheduler(
+    candle_store: Arc<Mutex<CandleStore>>,
+    config: Arc<Config>,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    grace: chrono::Duration,
+    is_backtest: bool,
+) {
+    loop {
+        let next_close_time = candle_store.lock().await.next_close_time();
+        let sleep = next_close_time
+            .and_then(|x| (x + grace - chrono::Utc::now()).to_std().ok())
+            .unwrap_or_default()
+            .min(MAX_SLEEP);
+        tokio::time::sleep(sleep).await;
+
+        let res = emit_closes(
+            &candle_store,
+            &config,
+            &redis_pool,
+            &pg_pool,
+            grace,
+            is_backtest,
+        )
+        .await;
+        if let Err(err) = res {
+            error!("{err:#}");
+        }
+    }
+}
+
+async fn emit_closes(
+    candle_store: &Arc<Mutex<CandleStore>>,
+    config: &Config,
+    redis_pool: &r2d2::Pool<redis::Client>,
+    pg_pool: &r2d2::Pool<ConnectionManager<PgConnection>>,
+    grace: chrono::Duration,
+    is_backtest: bool,
+) -> anyhow::Result<()> {
+    let mut candle_store = candle_store.lock().await;
+    let mut closed_candles = candle_store.close_expired(chrono::Utc::now() - grace)?;
+    if closed_candles.is_empty() {
+        return Ok(());
+    }
+    closed_candles.extend(candle_store.heikin_ashi(&closed_candles, config)?);
+
+    let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+    candle_store
+        .flush(pg_conn)
+        .context("Flushing candle_store on scheduled candle close")?;
+    let redis_conn = &mut redis_pool
+        .get()
+        .context("Getting connection from redis_pool")?;
+    publish_candles(
+        redis_conn,
+        &closed_candles,
+        Cow::Borrowed("candle_close"),
+        is_backtest,
+    )?;
+    return Ok(());
+}
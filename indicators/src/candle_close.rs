@@ -1,23 +1,51 @@
-use anyhow::Context;
-use diesel::{r2d2::ConnectionManager, PgConnection};
+use std::panic::AssertUnwindSafe;
+
+use anyhow::{anyhow, Context};
 use models::Candle;
+use tracing::error;
 
-use crate::{fvg::FvgIndicator, swing::SwingIndicator};
+use crate::{
+    config::Config,
+    registry::{IndicatorContext, Registry},
+};
 
 pub trait CandleCloseIndicator {
     fn process(&self, candle: &Candle) -> anyhow::Result<()>;
 }
 
+/// Runs the indicators configured for the candle's pair and timeframe, in order.
+///
+/// Each indicator runs in isolation: an error or a panic is logged and the
+/// next indicators still run.
 pub fn handle_candle_close(
     payload: String,
-    redis_pool: r2d2::Pool<redis::Client>,
-    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
-    is_backtest: bool,
+    registry: &Registry,
+    config: &Config,
+    context: IndicatorContext,
 ) -> anyhow::Result<()> {
     let data: Candle = serde_json::from_str(&payload).context("Parsing redis message to Candle")?;
-    let fvg_indicator = FvgIndicator::new(redis_pool.clone(), pg_pool.clone(), is_backtest);
-    let fractal_swing_indicator = SwingIndicator::new(redis_pool.clone(), pg_pool.clone(), is_backtest);
-    let _ = fvg_indicator.process(&data)?;
-    let _ = fractal_swing_indicator.process(&data)?;
+
+    for indicator in config.indicators(data.pair(), data.timeframe()) {
+        run_isolated(&indicator.name, &data, || {
+            return registry
+                .build(&indicator.name, &indicator.params, context.clone())?
+                .process(&data);
+        });
+    }
     return Ok(());
 }
+
+/// Runs `f` for the indicator `name` on `candle`, logging its error or panic.
+pub fn run_isolated<F: FnOnce() -> anyhow::Result<()>>(name: &str, candle: &Candle, f: F) {
+    let res = std::panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow!("Indicator panicked")));
+
+    if let Err(err) = res {
+        error!(
+            "{name} indicator on {} {} at {}: {err:#}",
+            candle.pair(),
+            candle.timeframe(),
+            candle.open_time()
+        );
+    }
+}
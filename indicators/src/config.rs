@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Indicators enabled per pair and timeframe, read from the file at `INDICATORS_CONFIG`.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Indicators run for pairs and timeframes without their own configuration.
+    indicators: Vec<IndicatorConfig>,
+    #[serde(default)]
+    products: HashMap<String, ProductConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductConfig {
+    indicators: Option<Vec<IndicatorConfig>>,
+    /// Indicators per timeframe string, e.g. `1h` or `renko-50`.
+    #[serde(default)]
+    timeframes: HashMap<String, Vec<IndicatorConfig>>,
+}
+
+/// An indicator registered under `name`, with its parameters.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndicatorConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub params: toml::Table,
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let path =
+            std::env::var("INDICATORS_CONFIG").unwrap_or_else(|_| "indicators.toml".to_owned());
+        let content =
+            std::fs::read_to_string(&path).context(format!("Reading config file {path}"))?;
+
+        return toml::from_str(&content).context(format!("Parsing config file {path}"));
+    }
+
+    pub fn indicators(&self, pair: &str, timeframe: &str) -> &[IndicatorConfig] {
+        let product = match self.products.get(pair) {
+            Some(product) => product,
+            None => return &self.indicators,
+        };
+        if let Some(indicators) = product.timeframes.get(timeframe) {
+            return indicators;
+        }
+        return product.indicators.as_deref().unwrap_or(&self.indicators);
+    }
+
+    /// Every configured indicator, to validate the configuration on startup.
+    pub fn all(&self) -> impl Iterator<Item = &IndicatorConfig> {
+        return self
+            .indicators
+            .iter()
+            .chain(self.products.values().flat_map(|x| {
+                x.indicators
+                    .iter()
+                    .flatten()
+                    .chain(x.timeframes.values().flatten())
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::{
+        divergence::DivergenceParams,
+        fvg::FvgFilter,
+        levels::LevelsParams,
+        registry::{parse_params, SwingParams},
+        volume_profile::VolumeProfileParams,
+    };
+
+    #[test]
+    fn default_config_is_valid() {
+        let config: Config = toml::from_str(include_str!("../../indicators.toml")).unwrap();
+
+        let names = |pair, timeframe| -> Vec<&str> {
+            return config
+                .indicators(pair, timeframe)
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect();
+        };
+        assert_eq!(
+            names("ETH-USD", "1h"),
+            vec![
+                "fvg",
+                "swing",
+                "market_structure",
+                "order_block",
+                "liquidity_sweep",
+                "dealing_range",
+                "ema",
+                "rsi",
+                "divergence",
+                "atr"
+            ]
+        );
+        assert_eq!(
+            names("BTC-USD", "1m"),
+            vec![
+                "fvg",
+                "swing",
+                "liquidity_sweep",
+                "levels",
+                "volume_profile",
+                "atr"
+            ]
+        );
+        assert!(names("BTC-USD", "4h").contains(&"macd"));
+        for indicator in config.all() {
+            match indicator.name.as_str() {
+                "fvg" => parse_params::<FvgFilter>(&indicator.params).map(|_| ()),
+                "swing" => parse_params::<SwingParams>(&indicator.params).map(|_| ()),
+                "levels" => parse_params::<LevelsParams>(&indicator.params).map(|_| ()),
+                "volume_profile" => {
+                    parse_params::<VolumeProfileParams>(&indicator.params).map(|_| ())
+                }
+                "divergence" => parse_params::<DivergenceParams>(&indicator.params)
+                    .map(|x| assert!(x.output().is_some())),
+                _ => Ok(()),
+            }
+            .unwrap();
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, upsert::excluded, PgConnection};
+use models::{
+    dealing_range::DealingRange,
+    indicator_value::{IndicatorValue, IndicatorValueBuilder},
+    schema::{indicator_values, swings},
+    swing::Swing,
+    Candle,
+};
+use redis::Commands;
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Computes the dealing range between the latest confirmed swing high and
+/// swing low of the candle's timeframe, whenever a candle confirms a new swing.
+///
+/// Its outputs are stored in `indicator_values` as `dealing_range` on the
+/// confirming candle, and the range is published on `dealing_range`. The swing
+/// indicator has to run before it on the same timeframe.
+pub struct DealingRangeIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+}
+
+impl DealingRangeIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+        };
+    }
+
+    /// Latest swing of `flow` confirmed by the close of `candle`.
+    fn get_last_swing(
+        &self,
+        candle: &Candle,
+        flow: &str,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Option<Swing>> {
+        let swing = swings::table
+            .filter(
+                swings::pair
+                    .eq(candle.pair())
+                    .and(swings::timeframe.eq(candle.timeframe()))
+                    .and(swings::flow.eq(flow))
+                    .and(swings::open_time.lt(candle.open_time()))
+                    .and(
+                        swings::confirmation_time
+                            .is_null()
+                            .or(swings::confirmation_time.le(candle.close_time())),
+                    ),
+            )
+            .select(Swing::as_select())
+            .order(swings::open_time.desc())
+            .first(pg_conn)
+            .optional()?;
+
+        return Ok(swing);
+    }
+
+    fn publish_range(&self, range: &DealingRange) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = match self.is_backtest {
+            false => "dealing_range",
+            true => "backtest-dealing_range",
+        };
+        let _: () = redis_conn
+            .publish(
+                channel,
+                serde_json::to_string(range).context(format!(
+                    "Stringify result for publishing on redis {channel}"
+                ))?,
+            )
+            .context(format!("Publishing to redis {channel} channel"))?;
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for DealingRangeIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        // Swing highs are `bear` swings and swing lows `bull` ones.
+        let high = self.get_last_swing(candle, "bear", pg_conn)?;
+        let low = self.get_last_swing(candle, "bull", pg_conn)?;
+        let range = match (high, low) {
+            (Some(high), Some(low)) if is_confirmed_by(candle, &[&high, &low]) => {
+                DealingRange::new(*candle.open_time(), &high, &low)
+            }
+            _ => None,
+        };
+        let range = match range {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+
+        let values = range
+            .outputs()
+            .into_iter()
+            .map(|(output, value)| {
+                return IndicatorValueBuilder::default()
+                    .pair(candle.pair().to_owned())
+                    .open_time(candle.open_time().to_owned())
+                    .timeframe(candle.timeframe().to_owned())
+                    .indicator("dealing_range".to_owned())
+                    .output(output.to_owned())
+                    .value(value)
+                    .build();
+            })
+            .collect::<Result<Vec<IndicatorValue>, _>>()?;
+        diesel::insert_into(indicator_values::table)
+            .values(&values)
+            .on_conflict((
+                indicator_values::pair,
+                indicator_values::open_time,
+                indicator_values::timeframe,
+                indicator_values::indicator,
+                indicator_values::output,
+            ))
+            .do_update()
+            .set(indicator_values::value.eq(excluded(indicator_values::value)))
+            .execute(pg_conn)?;
+        self.publish_range(&range)
+            .context("publishing dealing range")?;
+        return Ok(());
+    }
+}
+
+/// Whether one of `swings` was confirmed by the close of `candle`.
+fn is_confirmed_by(candle: &Candle, swings: &[&Swing]) -> bool {
+    return swings
+        .iter()
+        .any(|x| *x.confirmation_time() == Some(candle.close_time()));
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{
+        candle::CandleBuilder,
+        dealing_range::DealingRange,
+        swing::{Swing, SwingBuilder},
+        Candle,
+    };
+    use rust_decimal::Decimal;
+
+    use super::is_confirmed_by;
+
+    fn time(minute: u32) -> chrono::DateTime<chrono::Utc> {
+        return chrono::Utc
+            .with_ymd_and_hms(2024, 1, 1, 0, minute, 0)
+            .unwrap();
+    }
+
+    fn swing(minute: u32, price: i64, flow: &str) -> Swing {
+        return SwingBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(minute))
+            .timeframe("1m".to_owned())
+            .price(Decimal::from(price))
+            .flow(flow.to_owned())
+            .close_time(None)
+            .confirmation_time(Some(time(minute + 2)))
+            .build()
+            .unwrap();
+    }
+
+    fn candle(minute: u32) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(minute))
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(100))
+            .high(Decimal::from(100))
+            .low(Decimal::from(100))
+            .close(Decimal::from(100))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn premium_discount_and_ote() {
+        let low = swing(1, 100, "bull");
+        let high = swing(5, 200, "bear");
+        assert!(is_confirmed_by(&candle(6), &[&high, &low]));
+        assert!(!is_confirmed_by(&candle(7), &[&high, &low]));
+
+        // Leg up, retraced down from the high.
+        let range = DealingRange::new(time(6), &high, &low).unwrap();
+        assert_eq!(range.flow(), "bull");
+        assert_eq!(*range.equilibrium(), Decimal::from(150));
+        assert_eq!(*range.ote_62(), Decimal::from(138));
+        assert_eq!(*range.ote_705(), Decimal::new(1295, 1));
+        assert_eq!(*range.ote_79(), Decimal::from(121));
+        assert_eq!(range.zone(Decimal::from(130)), "discount");
+        assert_eq!(range.zone(Decimal::from(160)), "premium");
+        assert!(range.is_in_ote(Decimal::from(125)));
+        assert!(!range.is_in_ote(Decimal::from(140)));
+
+        // Leg down, retraced up from the low.
+        let range = DealingRange::new(time(8), &high, &swing(6, 100, "bull")).unwrap();
+        assert_eq!(range.flow(), "bear");
+        assert_eq!(*range.ote_62(), Decimal::from(162));
+        assert!(range.is_in_ote(Decimal::from(175)));
+
+        assert!(DealingRange::new(time(8), &swing(5, 90, "bear"), &low).is_none());
+    }
+}
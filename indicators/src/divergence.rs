@@ -0,0 +1,290 @@
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    schema::{indicator_values, swings},
+    swing::Swing,
+    Candle,
+};
+use redis::Commands;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Oscillator compared with the price at swing pivots, as stored in
+/// `indicator_values` by its indicator, e.g. `rsi-14` or `macd-12-26-9`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DivergenceParams {
+    #[serde(default = "DivergenceParams::default_oscillator")]
+    pub oscillator: String,
+}
+
+impl DivergenceParams {
+    fn default_oscillator() -> String {
+        return "rsi-14".to_owned();
+    }
+
+    /// Output of the oscillator compared, None when it is not supported.
+    pub fn output(&self) -> Option<&'static str> {
+        return match self.oscillator.split('-').next() {
+            Some("rsi") => Some("rsi"),
+            Some("macd") => Some("histogram"),
+            _ => None,
+        };
+    }
+}
+
+/// Divergence between the price and the oscillator on two consecutive swings,
+/// published on `divergence`.
+#[derive(Debug, Serialize)]
+struct Divergence<'a> {
+    /// `regular` or `hidden`.
+    kind: &'static str,
+    /// `bull` on swing lows, `bear` on swing highs.
+    flow: &'static str,
+    oscillator: &'a str,
+    previous_swing: Swing,
+    previous_value: Decimal,
+    swing: Swing,
+    value: Decimal,
+    candle: &'a Candle,
+}
+
+/// Detects regular and hidden divergences between the price and an oscillator
+/// on every swing confirmed by a candle, compared with the previous swing of
+/// the same flow.
+///
+/// The swing indicator and the oscillator have to run on the same timeframe,
+/// the oscillator being read on the pivot candles.
+pub struct DivergenceIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+    oscillator: String,
+    output: &'static str,
+}
+
+impl DivergenceIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+        oscillator: String,
+        output: &'static str,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+            oscillator,
+            output,
+        };
+    }
+
+    /// Swings confirmed by the close of `candle`.
+    fn get_confirmed_swings(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Swing>> {
+        let swings = swings::table
+            .filter(
+                swings::pair
+                    .eq(candle.pair())
+                    .and(swings::timeframe.eq(candle.timeframe()))
+                    .and(swings::confirmation_time.eq(candle.close_time())),
+            )
+            .select(Swing::as_select())
+            .get_results(pg_conn)?;
+
+        return Ok(swings);
+    }
+
+    /// Swing of the same flow before `swing`.
+    fn get_previous_swing(
+        &self,
+        swing: &Swing,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Option<Swing>> {
+        let previous = swings::table
+            .filter(
+                swings::pair
+                    .eq(swing.pair())
+                    .and(swings::timeframe.eq(swing.timeframe()))
+                    .and(swings::flow.eq(swing.flow()))
+                    .and(swings::open_time.lt(swing.open_time())),
+            )
+            .select(Swing::as_select())
+            .order(swings::open_time.desc())
+            .first(pg_conn)
+            .optional()?;
+
+        return Ok(previous);
+    }
+
+    /// Value of the oscillator on the pivot candle of `swing`.
+    fn get_value(
+        &self,
+        swing: &Swing,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Option<Decimal>> {
+        let value = indicator_values::table
+            .find((
+                swing.pair(),
+                swing.open_time(),
+                swing.timeframe(),
+                &self.oscillator,
+                self.output,
+            ))
+            .select(indicator_values::value)
+            .first(pg_conn)
+            .optional()?;
+
+        return Ok(value);
+    }
+
+    fn publish_divergences(&self, divergences: &[Divergence]) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = match self.is_backtest {
+            false => "divergence",
+            true => "backtest-divergence",
+        };
+        for divergence in divergences.iter() {
+            let _: () = redis_conn
+                .publish(
+                    channel,
+                    serde_json::to_string(divergence).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for DivergenceIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let mut divergences = Vec::new();
+        for swing in self.get_confirmed_swings(candle, pg_conn)? {
+            let previous_swing = match self.get_previous_swing(&swing, pg_conn)? {
+                Some(previous_swing) => previous_swing,
+                None => continue,
+            };
+            let (previous_value, value) = match (
+                self.get_value(&previous_swing, pg_conn)?,
+                self.get_value(&swing, pg_conn)?,
+            ) {
+                (Some(previous_value), Some(value)) => (previous_value, value),
+                _ => continue,
+            };
+            let (kind, flow) = match find_divergence(
+                swing.flow(),
+                (*previous_swing.price(), previous_value),
+                (*swing.price(), value),
+            ) {
+                Some(divergence) => divergence,
+                None => continue,
+            };
+            divergences.push(Divergence {
+                kind,
+                flow,
+                oscillator: &self.oscillator,
+                previous_swing,
+                previous_value,
+                swing,
+                value,
+                candle,
+            });
+        }
+
+        self.publish_divergences(&divergences)
+            .context("publishing divergences")?;
+        return Ok(());
+    }
+}
+
+/// Kind and flow of the divergence between two swings of `swing_flow`, given
+/// as (price, oscillator value), oldest first.
+///
+/// On swing highs (`bear` swings), a higher high with a lower oscillator high
+/// is a regular bearish divergence, a lower high with a higher oscillator high
+/// a hidden one. Swing lows are the other way around.
+fn find_divergence(
+    swing_flow: &str,
+    previous: (Decimal, Decimal),
+    current: (Decimal, Decimal),
+) -> Option<(&'static str, &'static str)> {
+    let price = current.0.cmp(&previous.0);
+    let oscillator = current.1.cmp(&previous.1);
+    if price == oscillator || price.is_eq() || oscillator.is_eq() {
+        return None;
+    }
+    let kind = match (swing_flow, price.is_gt()) {
+        ("bear", true) | ("bull", false) => "regular",
+        _ => "hidden",
+    };
+    let flow = match swing_flow {
+        "bear" => "bear",
+        _ => "bull",
+    };
+    return Some((kind, flow));
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{find_divergence, DivergenceParams};
+
+    fn pivot(price: i64, value: i64) -> (Decimal, Decimal) {
+        return (Decimal::from(price), Decimal::from(value));
+    }
+
+    #[test]
+    fn regular_and_hidden_divergences() {
+        assert_eq!(
+            find_divergence("bear", pivot(100, 70), pivot(110, 60)),
+            Some(("regular", "bear"))
+        );
+        assert_eq!(
+            find_divergence("bear", pivot(100, 60), pivot(90, 70)),
+            Some(("hidden", "bear"))
+        );
+        assert_eq!(
+            find_divergence("bull", pivot(100, 30), pivot(90, 40)),
+            Some(("regular", "bull"))
+        );
+        assert_eq!(
+            find_divergence("bull", pivot(100, 40), pivot(110, 30)),
+            Some(("hidden", "bull"))
+        );
+        assert_eq!(
+            find_divergence("bear", pivot(100, 60), pivot(110, 70)),
+            None
+        );
+        assert_eq!(
+            find_divergence("bull", pivot(100, 40), pivot(100, 30)),
+            None
+        );
+
+        let params: DivergenceParams = toml::from_str("oscillator = \"macd-12-26-9\"").unwrap();
+        assert_eq!(params.output(), Some("histogram"));
+        let params: DivergenceParams = toml::from_str("").unwrap();
+        assert_eq!(params.output(), Some("rsi"));
+    }
+}
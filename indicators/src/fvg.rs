@@ -3,18 +3,86 @@ use std::borrow::Cow;
 use anyhow::Context;
 use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
 use models::{
-    fvg::{FVGBuilder, FVG},
-    schema::{candles, fvgs},
+    fvg::{FVGBuilder, Mitigation, FVG},
+    schema::{candles, fvgs, indicator_values},
     Candle,
 };
 use redis::Commands;
+use rust_decimal::Decimal;
+use serde::Deserialize;
 
 use crate::candle_close::CandleCloseIndicator;
 
+/// Number of candles after its close during which an FVG can be inverted.
+const INVERSION_WINDOW: i64 = 100;
+
+/// Minimum size of an FVG and displacement of its middle candle. Every filter
+/// is optional, a gap has to pass all the configured ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FvgFilter {
+    /// Minimum size in price.
+    pub min_size: Option<Decimal>,
+    /// Minimum size in percent of the close of the candle forming the gap.
+    pub min_size_percent: Option<Decimal>,
+    /// Minimum size in multiples of the ATR of `atr_period` on the middle
+    /// candle, which the `atr` indicator has to compute on the same timeframe.
+    pub min_size_atr: Option<Decimal>,
+    #[serde(default = "FvgFilter::default_atr_period")]
+    pub atr_period: usize,
+    /// Minimum body of the middle candle in percent of its range.
+    pub displacement_body_percent: Option<Decimal>,
+    /// Stores the gaps that do not pass the filters, flagged as `filtered`.
+    #[serde(default)]
+    pub record_filtered: bool,
+}
+
+impl FvgFilter {
+    fn default_atr_period() -> usize {
+        return 14;
+    }
+
+    /// Whether a gap of `size` formed by `candle` passes the filters. A gap
+    /// does not pass the ATR filter while the ATR is unknown.
+    pub fn passes(
+        &self,
+        size: Decimal,
+        middle: &Candle,
+        candle: &Candle,
+        atr: Option<Decimal>,
+    ) -> bool {
+        if self.min_size.is_some_and(|x| size < x) {
+            return false;
+        }
+        if self
+            .min_size_percent
+            .is_some_and(|x| size < candle.close() * x / Decimal::ONE_HUNDRED)
+        {
+            return false;
+        }
+        if let Some(min_size_atr) = self.min_size_atr {
+            match atr {
+                Some(atr) if size >= atr * min_size_atr => (),
+                _ => return false,
+            }
+        }
+        if let Some(body_percent) = self.displacement_body_percent {
+            let range = middle.high() - middle.low();
+            let body = (middle.close() - middle.open()).abs();
+            if range.is_zero() || body * Decimal::ONE_HUNDRED < range * body_percent {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
 pub struct FvgIndicator {
     redis_pool: r2d2::Pool<redis::Client>,
     pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
     is_backtest: bool,
+    publish: bool,
+    filter: FvgFilter,
 }
 
 impl FvgIndicator {
@@ -22,20 +90,25 @@ impl FvgIndicator {
         redis_pool: r2d2::Pool<redis::Client>,
         pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
         is_backtest: bool,
+        publish: bool,
+        filter: FvgFilter,
     ) -> Self {
         return Self {
             redis_pool,
             pg_pool,
             is_backtest,
+            publish,
+            filter,
         };
     }
 
-    fn get_last_candle(
+    /// The two candles before `candle`, most recent first.
+    fn get_last_candles(
         &self,
         candle: &Candle,
         pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
-    ) -> anyhow::Result<Option<Candle>> {
-        let candle = candles::table
+    ) -> anyhow::Result<Vec<Candle>> {
+        let candles = candles::table
             .filter(
                 candles::pair
                     .eq(candle.pair())
@@ -44,12 +117,31 @@ impl FvgIndicator {
             )
             .select(Candle::as_select())
             .order(candles::open_time.desc())
-            .limit(1)
-            .offset(1)
-            .get_result(pg_conn)
+            .limit(2)
+            .get_results(pg_conn)?;
+
+        return Ok(candles);
+    }
+
+    fn get_atr(
+        &self,
+        middle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Option<Decimal>> {
+        let atr = indicator_values::table
+            .filter(
+                indicator_values::pair
+                    .eq(middle.pair())
+                    .and(indicator_values::timeframe.eq(middle.timeframe()))
+                    .and(indicator_values::indicator.eq(format!("atr-{}", self.filter.atr_period)))
+                    .and(indicator_values::output.eq("atr"))
+                    .and(indicator_values::open_time.eq(middle.open_time())),
+            )
+            .select(indicator_values::value)
+            .first(pg_conn)
             .optional()?;
 
-        return Ok(candle);
+        return Ok(atr);
     }
 
     fn handle_fvg_creation(
@@ -57,37 +149,51 @@ impl FvgIndicator {
         candle: &Candle,
         pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
     ) -> anyhow::Result<Option<FVG>> {
-        let last_candle = self.get_last_candle(candle, pg_conn)?;
-        if last_candle.is_none() {
-            println!("sdf: {candle:#?} {last_candle:#?}");
+        let last_candles = self.get_last_candles(candle, pg_conn)?;
+        if last_candles.len() != 2 {
             return Ok(None);
         }
 
-        let last_candle = last_candle.unwrap();
+        let (last_candle, middle) = (&last_candles[1], &last_candles[0]);
         let mut fvg_builder = FVGBuilder::default();
-        if last_candle.high() < candle.low() {
+        let size = if last_candle.high() < candle.low() {
             fvg_builder
                 .high(candle.low().to_owned())
                 .low(last_candle.high().to_owned())
                 .flow("bull".to_owned());
+            candle.low() - last_candle.high()
         } else if last_candle.low() > candle.high() {
             fvg_builder
                 .high(last_candle.low().to_owned())
                 .low(candle.high().to_owned())
                 .flow("bear".to_owned());
+            last_candle.low() - candle.high()
         } else {
             return Ok(None);
+        };
+
+        let atr = match self.filter.min_size_atr {
+            Some(_) => self.get_atr(middle, pg_conn)?,
+            None => None,
+        };
+        let filtered = !self.filter.passes(size, middle, candle, atr);
+        if filtered && !self.filter.record_filtered {
+            return Ok(None);
         }
 
         fvg_builder
             .pair(candle.pair().to_owned())
             .open_time(last_candle.open_time().to_owned())
             .timeframe(candle.timeframe().to_owned())
-            .close_time(None);
+            .close_time(None)
+            .filtered(filtered);
         let fvg = fvg_builder.build()?;
         let result: FVG = diesel::insert_into(fvgs::table)
             .values(fvg)
             .get_result(pg_conn)?;
+        if filtered {
+            return Ok(None);
+        }
         return Ok(Some(result));
     }
 
@@ -102,6 +208,7 @@ impl FvgIndicator {
                     .eq(candle.pair())
                     .and(fvgs::timeframe.eq(candle.timeframe()))
                     .and(fvgs::open_time.lt(candle.open_time()))
+                    .and(fvgs::filtered.eq(false))
                     .and(
                         fvgs::flow
                             .eq("bull")
@@ -117,11 +224,58 @@ impl FvgIndicator {
         return Ok(fvgs);
     }
 
-    fn publish_fvgs(
+    /// Updates the touch, fill and inversion of the FVGs formed before `candle`
+    /// that are still open and not fully filled, or closed within
+    /// `INVERSION_WINDOW` candles and not inverted yet.
+    fn handle_mitigated_fvgs(
         &self,
-        fvgs: Vec<FVG>,
-        channel: Cow<'static, str>,
-    ) -> anyhow::Result<()> {
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<(FVG, Mitigation)>> {
+        let closed_after = *candle.open_time()
+            - chrono::Duration::milliseconds(candle.size_in_millis() * INVERSION_WINDOW);
+        let fvgs: Vec<FVG> = fvgs::table
+            .filter(
+                fvgs::pair
+                    .eq(candle.pair())
+                    .and(fvgs::timeframe.eq(candle.timeframe()))
+                    .and(fvgs::open_time.lt(candle.open_time()))
+                    .and(fvgs::filtered.eq(false))
+                    .and(
+                        fvgs::close_time
+                            .is_null()
+                            .and(fvgs::fill_percent.lt(Decimal::ONE_HUNDRED))
+                            .or(fvgs::inversion_time
+                                .is_null()
+                                .and(fvgs::close_time.gt(closed_after))),
+                    ),
+            )
+            .select(FVG::as_select())
+            .get_results(pg_conn)?;
+
+        let mut mitigated = Vec::new();
+        for mut fvg in fvgs.into_iter() {
+            let mitigation = fvg.mitigate(candle);
+            if mitigation.is_empty() {
+                continue;
+            }
+            diesel::update(fvgs::table.find((fvg.pair(), fvg.open_time(), fvg.timeframe())))
+                .set((
+                    fvgs::touch_time.eq(fvg.touch_time()),
+                    fvgs::fill_percent.eq(fvg.fill_percent()),
+                    fvgs::ce_time.eq(fvg.ce_time()),
+                    fvgs::inversion_time.eq(fvg.inversion_time()),
+                ))
+                .execute(pg_conn)?;
+            mitigated.push((fvg, mitigation));
+        }
+        return Ok(mitigated);
+    }
+
+    fn publish_fvgs(&self, fvgs: Vec<FVG>, channel: Cow<'static, str>) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
         let redis_conn = &mut self
             .redis_pool
             .get()
@@ -132,7 +286,7 @@ impl FvgIndicator {
             channel
         };
         for fvg in fvgs.iter() {
-            redis_conn
+            let _: () = redis_conn
                 .publish(
                     channel.to_string(),
                     serde_json::to_string(fvg).context(format!(
@@ -151,14 +305,123 @@ impl CandleCloseIndicator for FvgIndicator {
             .pg_pool
             .get()
             .context("Getting connection from pg_pool")?;
+        let mitigated_fvgs = self.handle_mitigated_fvgs(candle, pg_conn)?;
         let new_fvg = self.handle_fvg_creation(candle, pg_conn)?;
         let closed_fvgs = self.handle_closed_fvgs(candle, pg_conn)?;
 
         // println!("new_fvg: {new_fvg:#?} closed_fvgs: {closed_fvgs:#?}");
         if let Some(new_fvg) = new_fvg {
-            self.publish_fvgs(vec![new_fvg], Cow::Borrowed("fvg")).context("publishing new_fvg")?;
+            self.publish_fvgs(vec![new_fvg], Cow::Borrowed("fvg"))
+                .context("publishing new_fvg")?;
         }
-        self.publish_fvgs(closed_fvgs, Cow::Borrowed("fvg_close")).context("publishing closed_fvgs")?;
+        self.publish_fvgs(closed_fvgs, Cow::Borrowed("fvg_close"))
+            .context("publishing closed_fvgs")?;
+        let mitigated = |event: fn(&Mitigation) -> bool| -> Vec<FVG> {
+            return mitigated_fvgs
+                .iter()
+                .filter(|(_, mitigation)| event(mitigation))
+                .map(|(fvg, _)| fvg.to_owned())
+                .collect();
+        };
+        self.publish_fvgs(mitigated(|x| x.touched), Cow::Borrowed("fvg_touch"))
+            .context("publishing touched fvgs")?;
+        self.publish_fvgs(mitigated(|x| x.filled), Cow::Borrowed("fvg_fill"))
+            .context("publishing filled fvgs")?;
+        self.publish_fvgs(mitigated(|x| x.encroached), Cow::Borrowed("fvg_ce"))
+            .context("publishing encroached fvgs")?;
+        self.publish_fvgs(mitigated(|x| x.inverted), Cow::Borrowed("fvg_inversion"))
+            .context("publishing inverted fvgs")?;
         return Ok(());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{candle::CandleBuilder, fvg::FVGBuilder, Candle};
+    use rust_decimal::Decimal;
+
+    use super::FvgFilter;
+
+    fn time(minute: u32) -> chrono::DateTime<chrono::Utc> {
+        return chrono::Utc
+            .with_ymd_and_hms(2024, 1, 1, 0, minute, 0)
+            .unwrap();
+    }
+
+    fn candle(minute: u32, high: i64, low: i64, close: i64) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(minute))
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(low))
+            .high(Decimal::from(high))
+            .low(Decimal::from(low))
+            .close(Decimal::from(close))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn touch_fill_and_inversion() {
+        let mut fvg = FVGBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(0))
+            .timeframe("1m".to_owned())
+            .high(Decimal::from(110))
+            .low(Decimal::from(100))
+            .flow("bull".to_owned())
+            .close_time(None)
+            .build()
+            .unwrap();
+
+        assert!(fvg.mitigate(&candle(3, 130, 111, 120)).is_empty());
+        let mitigation = fvg.mitigate(&candle(4, 120, 108, 115));
+        assert!(mitigation.touched && mitigation.filled && !mitigation.encroached);
+        assert_eq!(*fvg.fill_percent(), Decimal::from(20));
+        assert!(fvg.ce_time().is_none());
+
+        let mitigation = fvg.mitigate(&candle(5, 120, 104, 115));
+        assert!(!mitigation.touched && mitigation.filled && mitigation.encroached);
+        assert_eq!(*fvg.ce_time(), Some(time(5)));
+        assert!(!fvg.mitigate(&candle(6, 120, 106, 115)).filled);
+
+        let mut closed = FVGBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(0))
+            .timeframe("1m".to_owned())
+            .high(Decimal::from(110))
+            .low(Decimal::from(100))
+            .flow("bull".to_owned())
+            .close_time(Some(time(7)))
+            .build()
+            .unwrap();
+        assert!(closed.mitigate(&candle(8, 99, 90, 95)).is_empty());
+        assert!(closed.mitigate(&candle(9, 102, 94, 98)).inverted);
+        assert!(closed.mitigate(&candle(10, 102, 94, 98)).is_empty());
+    }
+
+    #[test]
+    fn size_and_displacement_filters() {
+        let middle = candle(1, 120, 100, 118);
+        let last = candle(2, 130, 112, 125);
+        let filter = FvgFilter {
+            min_size: Some(Decimal::TWO),
+            min_size_percent: Some(Decimal::ONE),
+            ..Default::default()
+        };
+        assert!(filter.passes(Decimal::TWO, &middle, &last, None));
+        assert!(!filter.passes(Decimal::ONE, &middle, &last, None));
+
+        let filter = FvgFilter {
+            min_size_atr: Some(Decimal::ONE),
+            displacement_body_percent: Some(Decimal::from(80)),
+            ..Default::default()
+        };
+        assert!(!filter.passes(Decimal::TEN, &middle, &last, None));
+        assert!(filter.passes(Decimal::TEN, &middle, &last, Some(Decimal::TEN)));
+        let middle = candle(1, 120, 100, 105);
+        assert!(!filter.passes(Decimal::TEN, &middle, &last, Some(Decimal::TEN)));
+    }
+}
@@ -0,0 +1,407 @@
+use std::borrow::Cow;
+
+use anyhow::Context;
+use chrono::{Datelike, Months, NaiveDate, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    level::{Level, LevelBuilder},
+    schema::levels,
+    Candle,
+};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Levels completed, and levels taken out with the side taken, `high` or `low`.
+type TakenLevels = (Vec<Level>, Vec<(Level, &'static str)>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        };
+    }
+
+    /// Start and end of the period containing `time`, weeks starting on Monday.
+    pub fn window(
+        &self,
+        time_zone: &Tz,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+        let date = time.with_timezone(time_zone).date_naive();
+        let (start, end) = match self {
+            Period::Day => (date, date.succ_opt()?),
+            Period::Week => {
+                let start =
+                    date - chrono::Duration::days(i64::from(date.weekday().num_days_from_monday()));
+                (start, start + chrono::Duration::days(7))
+            }
+            Period::Month => {
+                let start = date.with_day(1)?;
+                (start, start.checked_add_months(Months::new(1))?)
+            }
+        };
+
+        return Some((
+            local_time(time_zone, start, NaiveTime::MIN)?,
+            local_time(time_zone, end, NaiveTime::MIN)?,
+        ));
+    }
+}
+
+/// Trading session from `start` to `end` local time every day, crossing
+/// midnight when `end` is not after `start`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    pub name: String,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub time_zone: Tz,
+}
+
+impl Session {
+    fn new(name: &str, start: u32, end: u32, time_zone: Tz) -> Self {
+        return Self {
+            name: name.to_owned(),
+            start: NaiveTime::from_hms_opt(start / 100, start % 100, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(end / 100, end % 100, 0).unwrap_or_default(),
+            time_zone,
+        };
+    }
+
+    /// Start and end of the session containing `time`, if any.
+    pub fn window(
+        &self,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+        let date = time.with_timezone(&self.time_zone).date_naive();
+        for date in [date.pred_opt()?, date] {
+            let end_date = match self.end <= self.start {
+                true => date.succ_opt()?,
+                false => date,
+            };
+            let start = local_time(&self.time_zone, date, self.start)?;
+            let end = local_time(&self.time_zone, end_date, self.end)?;
+            if start <= time && time < end {
+                return Some((start, end));
+            }
+        }
+        return None;
+    }
+}
+
+/// Periods and sessions to keep levels of. Periods start at midnight in
+/// `time_zone`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelsParams {
+    #[serde(default = "LevelsParams::default_periods")]
+    pub periods: Vec<Period>,
+    #[serde(default = "LevelsParams::default_time_zone")]
+    pub time_zone: Tz,
+    #[serde(default = "LevelsParams::default_sessions")]
+    pub sessions: Vec<Session>,
+}
+
+impl LevelsParams {
+    fn default_periods() -> Vec<Period> {
+        return vec![Period::Day, Period::Week, Period::Month];
+    }
+
+    fn default_time_zone() -> Tz {
+        return Tz::UTC;
+    }
+
+    pub fn default_sessions() -> Vec<Session> {
+        return vec![
+            Session::new("asia", 900, 1800, Tz::Asia__Tokyo),
+            Session::new("london", 800, 1630, Tz::Europe__London),
+            Session::new("new_york", 930, 1600, Tz::America__New_York),
+        ];
+    }
+
+    /// Name, start and end of every period and session containing `time`.
+    pub fn windows(
+        &self,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<(
+        &str,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+    )> {
+        let periods = self.periods.iter().filter_map(|x| {
+            let (start, end) = x.window(&self.time_zone, time)?;
+            return Some((x.name(), start, end));
+        });
+        let sessions = self.sessions.iter().filter_map(|x| {
+            let (start, end) = x.window(time)?;
+            return Some((x.name.as_str(), start, end));
+        });
+
+        return periods.chain(sessions).collect();
+    }
+}
+
+/// Level whose high or low was traded through, published on `level_taken`.
+#[derive(Serialize)]
+struct LevelTaken<'a> {
+    level: &'a Level,
+    side: &'static str,
+    candle: &'a Candle,
+}
+
+/// Keeps the high and low of the periods and sessions of a pair in `levels`.
+///
+/// Levels are built from the candles opening during their period, so the
+/// indicator should run on a single timeframe dividing every session, e.g.
+/// `1m`. Completed levels are published on `level`, and the first candle
+/// trading through their high or low on `level_taken`.
+pub struct LevelsIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+    params: LevelsParams,
+}
+
+impl LevelsIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+        params: LevelsParams,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+            params,
+        };
+    }
+
+    fn upsert_level(
+        &self,
+        level: &Level,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<()> {
+        diesel::insert_into(levels::table)
+            .values(level)
+            .on_conflict((levels::pair, levels::open_time, levels::name))
+            .do_update()
+            .set(level)
+            .execute(pg_conn)?;
+        return Ok(());
+    }
+
+    /// Extends the levels of the periods and sessions `candle` opened in,
+    /// returning the ones it completed.
+    fn handle_level_updates(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Level>> {
+        let mut completed = Vec::new();
+        for (name, start, end) in self.params.windows(*candle.open_time()) {
+            let level = levels::table
+                .find((candle.pair(), start, name))
+                .select(Level::as_select())
+                .first(pg_conn)
+                .optional()?;
+            let mut level = match level {
+                Some(level) if *level.complete() => continue,
+                Some(level) => level,
+                None => LevelBuilder::default()
+                    .pair(candle.pair().to_owned())
+                    .open_time(start)
+                    .name(name.to_owned())
+                    .close_time(end)
+                    .high(candle.high().to_owned())
+                    .low(candle.low().to_owned())
+                    .build()?,
+            };
+            level.extend(candle);
+            self.upsert_level(&level, pg_conn)?;
+            if *level.complete() {
+                completed.push(level);
+            }
+        }
+        return Ok(completed);
+    }
+
+    /// Completes the levels whose period ended without a candle closing it and
+    /// takes out the highs and lows `candle` traded through.
+    ///
+    /// Only the levels `candle` changes are loaded, as the untaken levels of
+    /// a pair keep adding up during a trend.
+    fn handle_taken_levels(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<TakenLevels> {
+        let levels: Vec<Level> = levels::table
+            .filter(
+                levels::pair
+                    .eq(candle.pair())
+                    .and(levels::close_time.le(candle.open_time()))
+                    .and(
+                        levels::complete
+                            .eq(false)
+                            .or(levels::high_taken_time
+                                .is_null()
+                                .and(levels::high.lt(candle.high())))
+                            .or(levels::low_taken_time
+                                .is_null()
+                                .and(levels::low.gt(candle.low()))),
+                    ),
+            )
+            .select(Level::as_select())
+            .order(levels::open_time.asc())
+            .get_results(pg_conn)?;
+
+        let mut completed = Vec::new();
+        let mut taken = Vec::new();
+        for mut level in levels.into_iter() {
+            let newly_completed = level.complete_by(candle);
+            let sides = level.take(candle);
+            if !newly_completed && sides.is_empty() {
+                continue;
+            }
+            self.upsert_level(&level, pg_conn)?;
+            if newly_completed {
+                completed.push(level.clone());
+            }
+            taken.extend(sides.into_iter().map(|side| (level.clone(), side)));
+        }
+        return Ok((completed, taken));
+    }
+
+    fn publish<T: Serialize>(
+        &self,
+        values: &[T],
+        channel: Cow<'static, str>,
+    ) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = if self.is_backtest {
+            Cow::Owned(format!("backtest-{channel}"))
+        } else {
+            channel
+        };
+        for value in values.iter() {
+            let _: () = redis_conn
+                .publish(
+                    channel.to_string(),
+                    serde_json::to_string(value).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for LevelsIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let (mut completed, taken) = self.handle_taken_levels(candle, pg_conn)?;
+        completed.extend(self.handle_level_updates(candle, pg_conn)?);
+
+        let taken: Vec<LevelTaken> = taken
+            .iter()
+            .map(|(level, side)| LevelTaken {
+                level,
+                side,
+                candle,
+            })
+            .collect();
+        self.publish(&completed, Cow::Borrowed("level"))
+            .context("publishing completed levels")?;
+        self.publish(&taken, Cow::Borrowed("level_taken"))
+            .context("publishing taken levels")?;
+        return Ok(());
+    }
+}
+
+fn local_time(
+    time_zone: &Tz,
+    date: NaiveDate,
+    time: NaiveTime,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    return time_zone
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|x| x.with_timezone(&chrono::Utc));
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+
+    use super::{LevelsParams, Period};
+
+    #[test]
+    fn period_and_session_windows() {
+        let params: LevelsParams = toml::from_str(
+            r#"
+            time_zone = "America/New_York"
+            sessions = [{ name = "asia", start = "20:00:00", end = "00:00:00", time_zone = "America/New_York" }]
+            "#,
+        )
+        .unwrap();
+        // Wednesday 2024-07-03 01:00 UTC is Tuesday 21:00 in New York.
+        let time = chrono::Utc.with_ymd_and_hms(2024, 7, 3, 1, 0, 0).unwrap();
+        let windows = params.windows(time);
+        let window = |name: &str| {
+            return windows.iter().find(|x| x.0 == name).map(|x| (x.1, x.2));
+        };
+
+        let new_york = |day: u32, hour: u32| {
+            return Tz::America__New_York
+                .with_ymd_and_hms(2024, 7, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&chrono::Utc);
+        };
+        assert_eq!(window("day"), Some((new_york(2, 0), new_york(3, 0))));
+        assert_eq!(window("week"), Some((new_york(1, 0), new_york(8, 0))));
+        assert_eq!(window("asia"), Some((new_york(2, 20), new_york(3, 0))));
+        assert_eq!(
+            Period::Month.window(&Tz::UTC, time).unwrap().1,
+            chrono::Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap()
+        );
+
+        let default = LevelsParams {
+            periods: Vec::new(),
+            time_zone: Tz::UTC,
+            sessions: LevelsParams::default_sessions(),
+        };
+        // 14:00 UTC is in both the London and New York sessions in July.
+        let time = chrono::Utc.with_ymd_and_hms(2024, 7, 2, 14, 0, 0).unwrap();
+        let names: Vec<&str> = default.windows(time).iter().map(|x| x.0).collect();
+        assert_eq!(names, vec!["london", "new_york"]);
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{schema::swings, swing::Swing, Candle};
+use redis::Commands;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Wick through a swing by a candle closing back inside, published on
+/// `liquidity_sweep`.
+#[derive(Debug, Serialize)]
+struct LiquiditySweep<'a> {
+    swing: Swing,
+    candle: &'a Candle,
+    /// Distance the wick went past the swing price.
+    wick: Decimal,
+}
+
+/// Detects liquidity sweeps of the open swings of the candle's timeframe.
+///
+/// A swing high is swept by a candle trading above it and closing at or below
+/// it, a swing low by a candle trading below it and closing at or above it.
+/// The swing stays open and can be swept again.
+pub struct LiquiditySweepIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+}
+
+impl LiquiditySweepIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+        };
+    }
+
+    /// Swings still open and confirmed before `candle` closed.
+    fn get_open_swings(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Swing>> {
+        let swings = swings::table
+            .filter(
+                swings::pair
+                    .eq(candle.pair())
+                    .and(swings::timeframe.eq(candle.timeframe()))
+                    .and(swings::open_time.lt(candle.open_time()))
+                    .and(swings::close_time.is_null())
+                    .and(
+                        swings::confirmation_time
+                            .is_null()
+                            .or(swings::confirmation_time.lt(candle.close_time())),
+                    ),
+            )
+            .select(Swing::as_select())
+            .order(swings::open_time.asc())
+            .get_results(pg_conn)?;
+
+        return Ok(swings);
+    }
+
+    fn publish_sweeps(&self, sweeps: &[LiquiditySweep]) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = match self.is_backtest {
+            false => "liquidity_sweep",
+            true => "backtest-liquidity_sweep",
+        };
+        for sweep in sweeps.iter() {
+            let _: () = redis_conn
+                .publish(
+                    channel,
+                    serde_json::to_string(sweep).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for LiquiditySweepIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let sweeps: Vec<LiquiditySweep> = self
+            .get_open_swings(candle, pg_conn)?
+            .into_iter()
+            .filter_map(|swing| {
+                let wick = sweep_wick(&swing, candle)?;
+                return Some(LiquiditySweep {
+                    swing,
+                    candle,
+                    wick,
+                });
+            })
+            .collect();
+
+        self.publish_sweeps(&sweeps)
+            .context("publishing liquidity sweeps")?;
+        return Ok(());
+    }
+}
+
+/// Size of the wick of `candle` through `swing` when it swept it.
+fn sweep_wick(swing: &Swing, candle: &Candle) -> Option<Decimal> {
+    let price = *swing.price();
+    let wick = match swing.flow().as_str() {
+        "bear" if *candle.close() <= price => candle.high() - price,
+        "bull" if *candle.close() >= price => price - candle.low(),
+        _ => return None,
+    };
+    if wick <= Decimal::ZERO {
+        return None;
+    }
+    return Some(wick);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{candle::CandleBuilder, swing::SwingBuilder, Candle};
+    use rust_decimal::Decimal;
+
+    use super::sweep_wick;
+
+    fn candle(high: i64, low: i64, close: i64) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 5, 0).unwrap())
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(close))
+            .high(Decimal::from(high))
+            .low(Decimal::from(low))
+            .close(Decimal::from(close))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn wicks_through_swings() {
+        let swing = |price: i64, flow: &str| {
+            return SwingBuilder::default()
+                .pair("BTC-USD".to_owned())
+                .open_time(chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+                .timeframe("1m".to_owned())
+                .price(Decimal::from(price))
+                .flow(flow.to_owned())
+                .close_time(None)
+                .build()
+                .unwrap();
+        };
+        let high = swing(100, "bear");
+        let low = swing(90, "bull");
+
+        assert_eq!(
+            sweep_wick(&high, &candle(103, 95, 99)),
+            Some(Decimal::from(3))
+        );
+        assert_eq!(sweep_wick(&high, &candle(103, 95, 101)), None);
+        assert_eq!(sweep_wick(&high, &candle(100, 95, 99)), None);
+        assert_eq!(sweep_wick(&low, &candle(95, 88, 90)), Some(Decimal::TWO));
+        assert_eq!(sweep_wick(&low, &candle(95, 88, 89)), None);
+    }
+}
@@ -1,11 +1,25 @@
 mod candle_close;
+mod config;
+mod dealing_range;
+mod divergence;
 mod fvg;
+mod levels;
+mod liquidity_sweep;
+mod market_structure;
+mod order_block;
+mod recompute;
+mod registry;
 mod swing;
+mod technical;
+mod volume_profile;
 
-use std::future;
+use std::sync::Arc;
 
 use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use config::Config;
 use diesel::{r2d2::ConnectionManager, PgConnection};
+use registry::{IndicatorContext, Registry};
 use tracing::error;
 
 async fn handle_redis_message(msg: redis::Msg, state: AppState) -> anyhow::Result<()> {
@@ -15,14 +29,27 @@ async fn handle_redis_message(msg: redis::Msg, state: AppState) -> anyhow::Resul
     let payload: String = msg.get_payload()?;
 
     match channel.as_str() {
-        "candle_close" => {
-            candle_close::handle_candle_close(payload, state.redis_pool, state.pg_pool, false)?
-        }
+        "candle_close" => candle_close::handle_candle_close(
+            payload,
+            &state.registry,
+            &state.config,
+            IndicatorContext {
+                redis_pool: state.redis_pool,
+                pg_pool: state.pg_pool,
+                is_backtest: false,
+                publish: true,
+            },
+        )?,
         "backtest-candle_close" => candle_close::handle_candle_close(
             payload,
-            state.redis_pool,
-            state.pg_pool_backtest,
-            true,
+            &state.registry,
+            &state.config,
+            IndicatorContext {
+                redis_pool: state.redis_pool,
+                pg_pool: state.pg_pool_backtest,
+                is_backtest: true,
+                publish: true,
+            },
         )?,
         _ => bail!("No handler for redis channel {channel}"),
     };
@@ -55,19 +82,52 @@ fn init_redis_pool() -> anyhow::Result<r2d2::Pool<redis::Client>> {
         .context("Creating RedisPool");
 }
 
+#[derive(Debug, Parser)]
+#[command(about = "Updates indicators on candle close, or recomputes them from stored candles")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Replays stored candles through the configured indicators, rebuilding their results.
+    Recompute(recompute::RecomputeArgs),
+}
+
 pub struct AppState {
     pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
     pg_pool_backtest: r2d2::Pool<ConnectionManager<PgConnection>>,
     redis_pool: r2d2::Pool<redis::Client>,
+    registry: Arc<Registry>,
+    config: Arc<Config>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     dotenvy::dotenv()?;
     tracing_subscriber::fmt::init();
+    if let Some(Command::Recompute(args)) = cli.command {
+        return recompute::run(args, &Registry::default(), &Config::load()?);
+    }
+
     let pg_pool = init_pg_pool(false)?;
     let pg_pool_backtest = init_pg_pool(true)?;
     let redis_pool = init_redis_pool()?;
+    let registry = Arc::new(Registry::default());
+    let config = Arc::new(Config::load()?);
+    registry
+        .validate(
+            &config,
+            IndicatorContext {
+                redis_pool: redis_pool.clone(),
+                pg_pool: pg_pool.clone(),
+                is_backtest: false,
+                publish: true,
+            },
+        )
+        .context("Validating indicators config")?;
     let mut redis_sub_conn = redis_pool
         .get()
         .context("Get redis_sub_conn from redis_pool")?;
@@ -84,15 +144,14 @@ async fn main() -> anyhow::Result<()> {
             pg_pool,
             pg_pool_backtest,
             redis_pool,
+            registry: registry.clone(),
+            config: config.clone(),
         };
 
-        tokio::spawn(async {
-            let res = handle_redis_message(msg, state).await;
-
-            if let Err(err) = res {
-                error!("{err:#}");
-            }
-            future::ready(())
-        });
+        // Candle closes are handled one at a time, as indicators are updated
+        // incrementally from their value on the previous candle.
+        if let Err(err) = handle_redis_message(msg, state).await {
+            error!("{err:#}");
+        }
     }
 }
@@ -0,0 +1,258 @@
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    market_structure::{MarketStructure, MarketStructureBuilder, StructureBreak},
+    schema::{market_structures, structure_breaks, swings},
+    swing::Swing,
+    Candle,
+};
+use redis::Commands;
+use serde::Serialize;
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Break of structure published on `market_structure`.
+#[derive(Serialize)]
+struct StructureEvent<'a> {
+    #[serde(flatten)]
+    structure_break: &'a StructureBreak,
+    swing: Option<Swing>,
+    candle: &'a Candle,
+}
+
+/// Tracks the higher highs / lows of the swings of a timeframe and the trend
+/// they form, stored in `market_structures`.
+///
+/// A close through the last swing high or low is a break of structure (BOS)
+/// when it goes with the trend, or a change of character (CHoCH) when it
+/// reverses it. Breaks are stored in `structure_breaks`. The swing indicator
+/// has to run before it on the same timeframe.
+pub struct MarketStructureIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+}
+
+impl MarketStructureIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+        };
+    }
+
+    fn get_structure(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<MarketStructure> {
+        let structure = market_structures::table
+            .find((candle.pair(), candle.timeframe()))
+            .select(MarketStructure::as_select())
+            .first(pg_conn)
+            .optional()?;
+        if let Some(structure) = structure {
+            return Ok(structure);
+        }
+
+        let structure = MarketStructureBuilder::default()
+            .pair(candle.pair().to_owned())
+            .timeframe(candle.timeframe().to_owned())
+            .updated_time(*candle.open_time() - chrono::Duration::milliseconds(1))
+            .build()?;
+        return Ok(structure);
+    }
+
+    /// Swings of the candle's timeframe newer than the latest one applied to
+    /// `structure`, oldest first.
+    fn get_new_swings(
+        &self,
+        candle: &Candle,
+        structure: &MarketStructure,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Swing>> {
+        let mut query = swings::table
+            .filter(
+                swings::pair
+                    .eq(candle.pair())
+                    .and(swings::timeframe.eq(candle.timeframe()))
+                    .and(swings::open_time.lt(candle.open_time())),
+            )
+            .select(Swing::as_select())
+            .into_boxed();
+        if let Some(last_swing_time) = structure.last_swing_time() {
+            query = query.filter(swings::open_time.gt(last_swing_time));
+        }
+
+        return Ok(query.order(swings::open_time.asc()).get_results(pg_conn)?);
+    }
+
+    fn publish_breaks(
+        &self,
+        candle: &Candle,
+        breaks: &[StructureBreak],
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = match self.is_backtest {
+            false => "market_structure",
+            true => "backtest-market_structure",
+        };
+        for structure_break in breaks.iter() {
+            let swing = swings::table
+                .find((
+                    structure_break.pair(),
+                    structure_break.swing_time(),
+                    structure_break.timeframe(),
+                ))
+                .select(Swing::as_select())
+                .first(pg_conn)
+                .optional()?;
+            let event = StructureEvent {
+                structure_break,
+                swing,
+                candle,
+            };
+            let _: () = redis_conn
+                .publish(
+                    channel,
+                    serde_json::to_string(&event).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for MarketStructureIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let mut structure = self.get_structure(candle, pg_conn)?;
+        if structure.updated_time() >= candle.open_time() {
+            return Ok(());
+        }
+        let swings = self.get_new_swings(candle, &structure, pg_conn)?;
+        let breaks = update_structure(&mut structure, &swings, candle);
+
+        diesel::insert_into(market_structures::table)
+            .values(&structure)
+            .on_conflict((market_structures::pair, market_structures::timeframe))
+            .do_update()
+            .set(&structure)
+            .execute(pg_conn)?;
+        if breaks.is_empty() {
+            return Ok(());
+        }
+        diesel::insert_into(structure_breaks::table)
+            .values(&breaks)
+            .on_conflict_do_nothing()
+            .execute(pg_conn)?;
+        self.publish_breaks(candle, &breaks, pg_conn)
+            .context("publishing structure breaks")?;
+        return Ok(());
+    }
+}
+
+/// Applies the swings confirmed since the last candle, then `candle`.
+fn update_structure(
+    structure: &mut MarketStructure,
+    swings: &[Swing],
+    candle: &Candle,
+) -> Vec<StructureBreak> {
+    for swing in swings.iter() {
+        structure.add_swing(swing);
+    }
+    return structure.apply_candle(candle);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{
+        candle::CandleBuilder, market_structure::MarketStructureBuilder, swing::Swing,
+        swing::SwingBuilder, Candle,
+    };
+    use rust_decimal::Decimal;
+
+    use super::update_structure;
+
+    fn time(minute: u32) -> chrono::DateTime<chrono::Utc> {
+        return chrono::Utc
+            .with_ymd_and_hms(2024, 1, 1, 0, minute, 0)
+            .unwrap();
+    }
+
+    fn candle(minute: u32, close: i64) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(minute))
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(close))
+            .high(Decimal::from(close))
+            .low(Decimal::from(close))
+            .close(Decimal::from(close))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    fn swing(minute: u32, price: i64, flow: &str) -> Swing {
+        return SwingBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time(minute))
+            .timeframe("1m".to_owned())
+            .price(Decimal::from(price))
+            .flow(flow.to_owned())
+            .close_time(None)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn bos_then_choch() {
+        let mut structure = MarketStructureBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .timeframe("1m".to_owned())
+            .updated_time(time(0))
+            .build()
+            .unwrap();
+
+        let swings = [swing(1, 110, "bear"), swing(2, 100, "bull")];
+        assert!(update_structure(&mut structure, &swings, &candle(4, 105)).is_empty());
+        let breaks = update_structure(&mut structure, &[], &candle(5, 111));
+        assert_eq!(breaks[0].kind(), "bos");
+        assert_eq!(breaks[0].flow(), "bull");
+        assert_eq!(*breaks[0].swing_time(), time(1));
+        // A swing is only broken once.
+        assert!(update_structure(&mut structure, &[], &candle(6, 112)).is_empty());
+
+        let swings = [swing(6, 120, "bear"), swing(7, 105, "bull")];
+        assert!(update_structure(&mut structure, &swings, &candle(9, 110)).is_empty());
+        assert_eq!(structure.swing_high_label().as_deref(), Some("hh"));
+        assert_eq!(structure.swing_low_label().as_deref(), Some("hl"));
+        let breaks = update_structure(&mut structure, &[], &candle(10, 104));
+        assert_eq!(breaks[0].kind(), "choch");
+        assert_eq!(breaks[0].flow(), "bear");
+        assert_eq!(structure.trend().as_deref(), Some("bear"));
+        assert_eq!(*structure.updated_time(), time(10));
+    }
+}
@@ -0,0 +1,243 @@
+use std::borrow::Cow;
+
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    market_structure::StructureBreak,
+    order_block::{OrderBlock, OrderBlockBuilder},
+    schema::{candles, order_blocks, structure_breaks},
+    Candle,
+};
+use redis::Commands;
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Detects order blocks on the structure breaks of the market structure
+/// indicator, which has to run before it on the same timeframe.
+///
+/// The order block of a `bull` break is the last bearish candle between the
+/// broken swing high and the breaking candle, and the other way around for a
+/// `bear` break. It is mitigated by the first later candle trading into it.
+pub struct OrderBlockIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+}
+
+impl OrderBlockIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+        };
+    }
+
+    fn get_structure_breaks(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<StructureBreak>> {
+        let breaks = structure_breaks::table
+            .filter(
+                structure_breaks::pair
+                    .eq(candle.pair())
+                    .and(structure_breaks::timeframe.eq(candle.timeframe()))
+                    .and(structure_breaks::open_time.eq(candle.open_time())),
+            )
+            .select(StructureBreak::as_select())
+            .get_results(pg_conn)?;
+
+        return Ok(breaks);
+    }
+
+    /// Candles between the broken swing and `candle`, oldest first.
+    fn get_displacement_candles(
+        &self,
+        candle: &Candle,
+        structure_break: &StructureBreak,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let candles = candles::table
+            .filter(
+                candles::pair
+                    .eq(candle.pair())
+                    .and(candles::timeframe.eq(candle.timeframe()))
+                    .and(candles::open_time.gt(structure_break.swing_time()))
+                    .and(candles::open_time.lt(candle.open_time())),
+            )
+            .select(Candle::as_select())
+            .order(candles::open_time.asc())
+            .get_results(pg_conn)?;
+
+        return Ok(candles);
+    }
+
+    fn handle_order_block_creation(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<OrderBlock>> {
+        let mut order_blocks = Vec::new();
+        for structure_break in self.get_structure_breaks(candle, pg_conn)? {
+            let candles = self.get_displacement_candles(candle, &structure_break, pg_conn)?;
+            let origin = match find_order_block(&candles, structure_break.flow()) {
+                Some(origin) => origin,
+                None => continue,
+            };
+            let order_block = OrderBlockBuilder::default()
+                .pair(candle.pair().to_owned())
+                .open_time(origin.open_time().to_owned())
+                .timeframe(candle.timeframe().to_owned())
+                .high(origin.high().to_owned())
+                .low(origin.low().to_owned())
+                .flow(structure_break.flow().to_owned())
+                .break_time(candle.open_time().to_owned())
+                .build()?;
+            let result: Option<OrderBlock> = diesel::insert_into(order_blocks::table)
+                .values(order_block)
+                .on_conflict_do_nothing()
+                .get_result(pg_conn)
+                .optional()?;
+            order_blocks.extend(result);
+        }
+        return Ok(order_blocks);
+    }
+
+    fn handle_mitigated_order_blocks(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<OrderBlock>> {
+        let order_blocks = diesel::update(
+            order_blocks::table.filter(
+                order_blocks::pair
+                    .eq(candle.pair())
+                    .and(order_blocks::timeframe.eq(candle.timeframe()))
+                    .and(order_blocks::break_time.lt(candle.open_time()))
+                    .and(
+                        order_blocks::flow
+                            .eq("bull")
+                            .and(order_blocks::high.ge(candle.low()))
+                            .or(order_blocks::flow
+                                .eq("bear")
+                                .and(order_blocks::low.le(candle.high()))),
+                    )
+                    .and(order_blocks::mitigation_time.is_null()),
+            ),
+        )
+        .set(order_blocks::mitigation_time.eq(candle.open_time()))
+        .get_results(pg_conn)?;
+
+        return Ok(order_blocks);
+    }
+
+    fn publish_order_blocks(
+        &self,
+        order_blocks: Vec<OrderBlock>,
+        channel: Cow<'static, str>,
+    ) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = if self.is_backtest {
+            Cow::Owned(format!("backtest-{channel}"))
+        } else {
+            channel
+        };
+        for order_block in order_blocks.iter() {
+            let _: () = redis_conn
+                .publish(
+                    channel.to_string(),
+                    serde_json::to_string(order_block).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for OrderBlockIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let mitigated_order_blocks = self.handle_mitigated_order_blocks(candle, pg_conn)?;
+        let new_order_blocks = self.handle_order_block_creation(candle, pg_conn)?;
+
+        self.publish_order_blocks(new_order_blocks, Cow::Borrowed("order_block"))
+            .context("publishing new_order_blocks")?;
+        self.publish_order_blocks(
+            mitigated_order_blocks,
+            Cow::Borrowed("order_block_mitigated"),
+        )
+        .context("publishing mitigated_order_blocks")?;
+        return Ok(());
+    }
+}
+
+/// Last candle of `candles` closing against `flow`.
+fn find_order_block<'a>(candles: &'a [Candle], flow: &str) -> Option<&'a Candle> {
+    return candles.iter().rev().find(|x| match flow {
+        "bull" => x.close() < x.open(),
+        _ => x.close() > x.open(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{candle::CandleBuilder, Candle};
+    use rust_decimal::Decimal;
+
+    use super::find_order_block;
+
+    fn candle(minute: u32, open: i64, close: i64) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(
+                chrono::Utc
+                    .with_ymd_and_hms(2024, 1, 1, 0, minute, 0)
+                    .unwrap(),
+            )
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(open))
+            .high(Decimal::from(open.max(close)))
+            .low(Decimal::from(open.min(close)))
+            .close(Decimal::from(close))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn last_opposite_candle() {
+        let candles = [
+            candle(0, 100, 95),
+            candle(1, 95, 90),
+            candle(2, 90, 100),
+            candle(3, 100, 120),
+        ];
+        let order_block = find_order_block(&candles, "bull").unwrap();
+        assert_eq!(*order_block.open(), Decimal::from(95));
+        assert_eq!(*order_block.low(), Decimal::from(90));
+
+        assert!(find_order_block(&candles[2..], "bull").is_none());
+        let order_block = find_order_block(&candles, "bear").unwrap();
+        assert_eq!(*order_block.close(), Decimal::from(120));
+    }
+}
@@ -0,0 +1,376 @@
+use anyhow::{ensure, Context};
+use diesel::{
+    connection::SimpleConnection,
+    prelude::*,
+    r2d2::{ConnectionManager, CustomizeConnection},
+    PgConnection,
+};
+use models::{
+    schema::{
+        candles, fvgs, indicator_values, levels, market_structures, order_blocks, structure_breaks,
+        swings, volume_profile_bins, volume_profiles,
+    },
+    Candle,
+};
+use tracing::info;
+
+use crate::{
+    candle_close::{run_isolated, CandleCloseIndicator},
+    config::Config,
+    registry::{IndicatorContext, Registry},
+};
+
+/// Tables the indicators write to, created in the scratch schema, with the
+/// time column their results before the replayed range are copied by. The
+/// market structure only has its latest state, it is rebuilt from the swings.
+const RESULT_TABLES: [(&str, Option<&str>); 9] = [
+    ("fvgs", Some("open_time")),
+    ("swings", Some("open_time")),
+    ("indicator_values", Some("open_time")),
+    ("market_structures", None),
+    ("structure_breaks", Some("open_time")),
+    ("order_blocks", Some("break_time")),
+    ("levels", Some("open_time")),
+    ("volume_profiles", Some("open_time")),
+    ("volume_profile_bins", Some("open_time")),
+];
+
+/// Indicators storing their outputs in `indicator_values`.
+const INDICATOR_VALUES_WRITERS: [&str; 7] = [
+    "sma",
+    "ema",
+    "rsi",
+    "atr",
+    "macd",
+    "bollinger",
+    "dealing_range",
+];
+
+#[derive(Debug, clap::Args)]
+pub struct RecomputeArgs {
+    #[arg(long)]
+    pub pair: String,
+    /// Comma separated timeframes, e.g. `5m,1h`.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub timeframes: Vec<String>,
+    /// Open time of the first candle replayed, e.g. `2024-06-01T00:00:00Z`.
+    #[arg(long)]
+    pub start: chrono::DateTime<chrono::Utc>,
+    /// Open time of the last candle replayed.
+    #[arg(long)]
+    pub end: chrono::DateTime<chrono::Utc>,
+    /// Writes the results to the tables of this schema, created from the live
+    /// ones, instead of the live tables. Candles are still read from `public`.
+    #[arg(long)]
+    pub schema: Option<String>,
+    /// Uses the backtest database.
+    #[arg(long)]
+    pub backtest: bool,
+    /// Publishes the results on redis like on a live candle close.
+    #[arg(long)]
+    pub publish: bool,
+}
+
+/// Sets the schema and disables synchronous commits on every connection, as
+/// recomputed results can be recomputed again if lost.
+#[derive(Debug)]
+struct BulkSession {
+    schema: Option<String>,
+}
+
+impl CustomizeConnection<PgConnection, diesel::r2d2::Error> for BulkSession {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+        let mut sql = "set synchronous_commit to off;".to_owned();
+        if let Some(schema) = self.schema.as_ref() {
+            sql.push_str(&format!("set search_path to {schema}, public;"));
+        }
+        return conn
+            .batch_execute(&sql)
+            .map_err(diesel::r2d2::Error::QueryError);
+    }
+}
+
+fn init_pg_pool(
+    args: &RecomputeArgs,
+) -> anyhow::Result<r2d2::Pool<ConnectionManager<PgConnection>>> {
+    let database_url = match args.backtest {
+        false => std::env::var("DATABASE_URL").context("DATABASE_URL from .env file")?,
+        true => std::env::var("BACKTEST_DATABASE_URL")
+            .context("BACKTEST_DATABASE_URL from .env file")?,
+    };
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+
+    return r2d2::Pool::builder()
+        .max_size(2)
+        .connection_customizer(Box::new(BulkSession {
+            schema: args.schema.clone(),
+        }))
+        .build(manager)
+        .context("Creating PgPool for recompute");
+}
+
+/// Whether `schema` can be used unquoted in SQL.
+fn is_valid_schema(schema: &str) -> bool {
+    return schema != "public"
+        && schema.starts_with(|x: char| x.is_ascii_lowercase() || x == '_')
+        && schema
+            .chars()
+            .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '_');
+}
+
+fn create_schema(schema: &str, pg_conn: &mut PgConnection) -> anyhow::Result<()> {
+    let mut sql = format!("create schema if not exists {schema};");
+    for (table, _) in RESULT_TABLES.iter() {
+        sql.push_str(&format!(
+            "create table if not exists {schema}.{table} (like public.{table} including all);"
+        ));
+    }
+    return pg_conn
+        .batch_execute(&sql)
+        .context(format!("Creating schema {schema}"));
+}
+
+/// Copies the results of `args.pair` from before `args.start` to the tables of
+/// `schema`, so the replay goes on from the same state as in the live tables.
+fn copy_history(
+    schema: &str,
+    args: &RecomputeArgs,
+    pg_conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    for (table, time_column) in RESULT_TABLES.iter() {
+        let time_column = match time_column {
+            Some(time_column) => time_column,
+            None => continue,
+        };
+        diesel::sql_query(format!(
+            "insert into {schema}.{table} select * from public.{table} \
+            where pair = $1 and {time_column} < $2 on conflict do nothing"
+        ))
+        .bind::<diesel::sql_types::Text, _>(&args.pair)
+        .bind::<diesel::sql_types::Timestamptz, _>(args.start)
+        .execute(pg_conn)
+        .context(format!("Copying {table} to schema {schema}"))?;
+    }
+    return Ok(());
+}
+
+/// Deletes the results of the replayed candles, so they are rebuilt from the
+/// results before `start`. Results are only deleted when one of `names`, the
+/// indicators replayed on each timeframe, builds them.
+fn clear_results(
+    args: &RecomputeArgs,
+    names: &[(&String, &str)],
+    pg_conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let pair = args.pair.as_str();
+    let (start, end) = (args.start, args.end);
+    let replays_any = |name: &str| names.iter().any(|(_, x)| *x == name);
+    for timeframe in args.timeframes.iter() {
+        let replays = |name: &str| names.contains(&(timeframe, name));
+        if replays("fvg") {
+            diesel::delete(
+                fvgs::table.filter(
+                    fvgs::pair
+                        .eq(pair)
+                        .and(fvgs::timeframe.eq(timeframe))
+                        .and(fvgs::open_time.between(start, end)),
+                ),
+            )
+            .execute(pg_conn)?;
+        }
+        if replays("swing") {
+            diesel::delete(
+                swings::table.filter(
+                    swings::pair
+                        .eq(pair)
+                        .and(swings::timeframe.eq(timeframe))
+                        .and(swings::open_time.between(start, end)),
+                ),
+            )
+            .execute(pg_conn)?;
+        }
+        if INDICATOR_VALUES_WRITERS.iter().any(|x| replays(x)) {
+            diesel::delete(
+                indicator_values::table.filter(
+                    indicator_values::pair
+                        .eq(pair)
+                        .and(indicator_values::timeframe.eq(timeframe))
+                        .and(indicator_values::open_time.between(start, end)),
+                ),
+            )
+            .execute(pg_conn)?;
+        }
+        if replays("market_structure") {
+            diesel::delete(
+                structure_breaks::table.filter(
+                    structure_breaks::pair
+                        .eq(pair)
+                        .and(structure_breaks::timeframe.eq(timeframe))
+                        .and(structure_breaks::open_time.between(start, end)),
+                ),
+            )
+            .execute(pg_conn)?;
+            // The structure only has its latest state, it is rebuilt from the swings.
+            diesel::delete(market_structures::table.find((pair, timeframe))).execute(pg_conn)?;
+        }
+        if replays("order_block") {
+            diesel::delete(
+                order_blocks::table.filter(
+                    order_blocks::pair
+                        .eq(pair)
+                        .and(order_blocks::timeframe.eq(timeframe))
+                        .and(order_blocks::break_time.between(start, end)),
+                ),
+            )
+            .execute(pg_conn)?;
+        }
+    }
+    if replays_any("levels") {
+        diesel::delete(
+            levels::table.filter(
+                levels::pair
+                    .eq(pair)
+                    .and(levels::open_time.between(start, end)),
+            ),
+        )
+        .execute(pg_conn)?;
+    }
+    if replays_any("volume_profile") {
+        diesel::delete(
+            volume_profile_bins::table.filter(
+                volume_profile_bins::pair
+                    .eq(pair)
+                    .and(volume_profile_bins::open_time.between(start, end)),
+            ),
+        )
+        .execute(pg_conn)?;
+        diesel::delete(
+            volume_profiles::table.filter(
+                volume_profiles::pair
+                    .eq(pair)
+                    .and(volume_profiles::open_time.between(start, end)),
+            ),
+        )
+        .execute(pg_conn)?;
+    }
+    return Ok(());
+}
+
+/// Replays the stored candles of `args.pair` through the indicators configured
+/// for each timeframe, in the order they closed.
+pub fn run(args: RecomputeArgs, registry: &Registry, config: &Config) -> anyhow::Result<()> {
+    ensure!(args.start <= args.end, "start must not be after end");
+    if let Some(schema) = args.schema.as_ref() {
+        ensure!(
+            is_valid_schema(schema),
+            "schema must be lowercase alphanumeric and not public"
+        );
+    }
+    let pg_pool = init_pg_pool(&args)?;
+    let redis_pool = match args.publish {
+        true => crate::init_redis_pool()?,
+        // Never connected to, as nothing is published.
+        false => r2d2::Pool::builder()
+            .min_idle(Some(0))
+            .build_unchecked(redis::Client::open("redis://127.0.0.1/")?),
+    };
+    let context = IndicatorContext {
+        redis_pool,
+        pg_pool: pg_pool.clone(),
+        is_backtest: args.backtest,
+        publish: args.publish,
+    };
+
+    let mut indicators: Vec<(&String, &str, Box<dyn CandleCloseIndicator>)> = Vec::new();
+    for timeframe in args.timeframes.iter() {
+        for indicator in config.indicators(&args.pair, timeframe) {
+            let built = registry.build(&indicator.name, &indicator.params, context.clone())?;
+            indicators.push((timeframe, indicator.name.as_str(), built));
+        }
+    }
+    let names: Vec<(&String, &str)> = indicators
+        .iter()
+        .map(|(timeframe, name, _)| (*timeframe, *name))
+        .collect();
+
+    let pg_conn = &mut pg_pool.get().context("Getting connection from pg_pool")?;
+    if let Some(schema) = args.schema.as_ref() {
+        create_schema(schema, pg_conn)?;
+        copy_history(schema, &args, pg_conn)?;
+    }
+    clear_results(&args, &names, pg_conn).context("Clearing recomputed results")?;
+
+    let mut candles: Vec<Candle> = candles::table
+        .filter(
+            candles::pair
+                .eq(&args.pair)
+                .and(candles::timeframe.eq_any(&args.timeframes))
+                .and(candles::open_time.between(args.start, args.end)),
+        )
+        .select(Candle::as_select())
+        .get_results(pg_conn)?;
+    candles.sort_by_key(|x| (x.close_time(), *x.size_in_millis()));
+    info!(
+        "Recomputing {} {:?} indicators on {} candles from {} to {}",
+        args.pair,
+        args.timeframes,
+        candles.len(),
+        args.start,
+        args.end
+    );
+
+    for (i, candle) in candles.iter().enumerate() {
+        for (timeframe, name, indicator) in indicators.iter() {
+            if *timeframe != candle.timeframe() {
+                continue;
+            }
+            run_isolated(name, candle, || indicator.process(candle));
+        }
+        if (i + 1) % 10_000 == 0 {
+            info!("Recomputed {} candles", i + 1);
+        }
+    }
+    info!("Recomputed {} candles", candles.len());
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::is_valid_schema;
+    use crate::{Cli, Command};
+
+    #[test]
+    fn parses_recompute_args() {
+        let cli = Cli::try_parse_from([
+            "indicators",
+            "recompute",
+            "--pair",
+            "BTC-USD",
+            "--timeframes",
+            "5m,1h",
+            "--start",
+            "2024-06-01T00:00:00Z",
+            "--end",
+            "2024-06-30T00:00:00Z",
+            "--schema",
+            "scratch",
+        ])
+        .unwrap();
+        let args = match cli.command {
+            Some(Command::Recompute(args)) => args,
+            _ => panic!("expected recompute"),
+        };
+        assert_eq!(args.timeframes, vec!["5m", "1h"]);
+        assert!(!args.publish && !args.backtest);
+
+        assert!(Cli::try_parse_from(["indicators"])
+            .unwrap()
+            .command
+            .is_none());
+        assert!(is_valid_schema("scratch_1"));
+        assert!(!is_valid_schema("public"));
+        assert!(!is_valid_schema("x; drop table candles"));
+    }
+}
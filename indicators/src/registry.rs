@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, ensure, Context};
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    candle_close::CandleCloseIndicator,
+    config::Config,
+    dealing_range::DealingRangeIndicator,
+    divergence::{DivergenceIndicator, DivergenceParams},
+    fvg::{FvgFilter, FvgIndicator},
+    levels::{LevelsIndicator, LevelsParams},
+    liquidity_sweep::LiquiditySweepIndicator,
+    market_structure::MarketStructureIndicator,
+    order_block::OrderBlockIndicator,
+    swing::SwingIndicator,
+    technical::{Technical, TechnicalIndicator},
+    volume_profile::{VolumeProfileIndicator, VolumeProfileParams},
+};
+
+/// Connections and mode an indicator is built with.
+#[derive(Clone)]
+pub struct IndicatorContext {
+    pub redis_pool: r2d2::Pool<redis::Client>,
+    pub pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    pub is_backtest: bool,
+    /// Publishes the results on redis, disabled when recomputing history.
+    pub publish: bool,
+}
+
+/// Builds an indicator from its configured parameters.
+pub type IndicatorFactory =
+    fn(&toml::Table, IndicatorContext) -> anyhow::Result<Box<dyn CandleCloseIndicator>>;
+
+/// Parameters of indicators without any.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoParams {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeriodParams {
+    pub period: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacdParams {
+    #[serde(default = "MacdParams::default_fast")]
+    pub fast: usize,
+    #[serde(default = "MacdParams::default_slow")]
+    pub slow: usize,
+    #[serde(default = "MacdParams::default_signal")]
+    pub signal: usize,
+}
+
+impl MacdParams {
+    fn default_fast() -> usize {
+        return 12;
+    }
+
+    fn default_slow() -> usize {
+        return 26;
+    }
+
+    fn default_signal() -> usize {
+        return 9;
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BollingerParams {
+    #[serde(default = "BollingerParams::default_period")]
+    pub period: usize,
+    #[serde(default = "BollingerParams::default_std_dev")]
+    pub std_dev: Decimal,
+}
+
+impl BollingerParams {
+    fn default_period() -> usize {
+        return 20;
+    }
+
+    fn default_std_dev() -> Decimal {
+        return Decimal::TWO;
+    }
+}
+
+/// Bars on each side of a swing pivot, and the tolerance within which highs
+/// or lows are equal.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwingParams {
+    #[serde(default = "SwingParams::default_bars")]
+    pub left: usize,
+    #[serde(default = "SwingParams::default_bars")]
+    pub right: usize,
+    #[serde(default)]
+    pub tolerance_percent: Decimal,
+}
+
+impl SwingParams {
+    fn default_bars() -> usize {
+        return 1;
+    }
+}
+
+/// Deserializes the parameters of an indicator, rejecting unknown ones.
+pub fn parse_params<T: DeserializeOwned>(params: &toml::Table) -> anyhow::Result<T> {
+    return params
+        .clone()
+        .try_into()
+        .context(format!("Parsing indicator params {params}"));
+}
+
+/// Indicators that can be enabled in the configuration, by name.
+pub struct Registry {
+    factories: HashMap<&'static str, IndicatorFactory>,
+}
+
+impl Registry {
+    pub fn register(&mut self, name: &'static str, factory: IndicatorFactory) {
+        self.factories.insert(name, factory);
+    }
+
+    pub fn build(
+        &self,
+        name: &str,
+        params: &toml::Table,
+        context: IndicatorContext,
+    ) -> anyhow::Result<Box<dyn CandleCloseIndicator>> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| anyhow!("No indicator registered as {name}"))?;
+
+        return factory(params, context).context(format!("Building indicator {name}"));
+    }
+
+    /// Builds every configured indicator once so that unknown names and invalid
+    /// parameters are reported on startup.
+    pub fn validate(&self, config: &Config, context: IndicatorContext) -> anyhow::Result<()> {
+        for indicator in config.all() {
+            self.build(&indicator.name, &indicator.params, context.clone())?;
+        }
+        return Ok(());
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        registry.register("fvg", |params, context| {
+            let filter: FvgFilter = parse_params(params)?;
+            ensure!(filter.atr_period > 0, "fvg atr_period must be positive");
+            return Ok(Box::new(FvgIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+                filter,
+            )));
+        });
+        registry.register("swing", |params, context| {
+            let params: SwingParams = parse_params(params)?;
+            ensure!(
+                params.left > 0 && params.right > 0,
+                "swing left and right bar counts must be positive"
+            );
+            return Ok(Box::new(SwingIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+                params.left,
+                params.right,
+                params.tolerance_percent,
+            )));
+        });
+        registry.register("market_structure", |params, context| {
+            parse_params::<NoParams>(params)?;
+            return Ok(Box::new(MarketStructureIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+            )));
+        });
+        registry.register("order_block", |params, context| {
+            parse_params::<NoParams>(params)?;
+            return Ok(Box::new(OrderBlockIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+            )));
+        });
+        registry.register("liquidity_sweep", |params, context| {
+            parse_params::<NoParams>(params)?;
+            return Ok(Box::new(LiquiditySweepIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+            )));
+        });
+        registry.register("dealing_range", |params, context| {
+            parse_params::<NoParams>(params)?;
+            return Ok(Box::new(DealingRangeIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+            )));
+        });
+        registry.register("divergence", |params, context| {
+            let params: DivergenceParams = parse_params(params)?;
+            let output = params.output().ok_or_else(|| {
+                anyhow!(
+                    "divergence oscillator {} must be an rsi or macd",
+                    params.oscillator
+                )
+            })?;
+            return Ok(Box::new(DivergenceIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+                params.oscillator,
+                output,
+            )));
+        });
+        registry.register("levels", |params, context| {
+            let params: LevelsParams = parse_params(params)?;
+            let mut names: Vec<&str> = params
+                .periods
+                .iter()
+                .map(|x| x.name())
+                .chain(params.sessions.iter().map(|x| x.name.as_str()))
+                .collect();
+            let count = names.len();
+            names.sort();
+            names.dedup();
+            ensure!(names.len() == count, "levels names must be unique");
+            return Ok(Box::new(LevelsIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+                params,
+            )));
+        });
+        registry.register("volume_profile", |params, context| {
+            let params: VolumeProfileParams = parse_params(params)?;
+            ensure!(
+                params.bin_size > Decimal::ZERO,
+                "volume_profile bin_size must be positive"
+            );
+            ensure!(
+                params.value_area_percent > Decimal::ZERO
+                    && params.value_area_percent <= Decimal::ONE_HUNDRED,
+                "volume_profile value_area_percent must be in (0, 100]"
+            );
+            let mut names: Vec<&str> = params
+                .periods
+                .iter()
+                .map(|x| x.name())
+                .chain(params.sessions.iter().map(|x| x.name.as_str()))
+                .chain(params.ranges.iter().map(|x| x.name.as_str()))
+                .collect();
+            let count = names.len();
+            names.sort();
+            names.dedup();
+            ensure!(names.len() == count, "volume_profile names must be unique");
+            return Ok(Box::new(VolumeProfileIndicator::new(
+                context.redis_pool,
+                context.pg_pool,
+                context.is_backtest,
+                context.publish,
+                params,
+            )));
+        });
+
+        registry.register("sma", |params, context| {
+            let params: PeriodParams = parse_params(params)?;
+            return technical(
+                Technical::Sma {
+                    period: params.period,
+                },
+                context,
+            );
+        });
+        registry.register("ema", |params, context| {
+            let params: PeriodParams = parse_params(params)?;
+            return technical(
+                Technical::Ema {
+                    period: params.period,
+                },
+                context,
+            );
+        });
+        registry.register("rsi", |params, context| {
+            let params: PeriodParams = parse_params(params)?;
+            return technical(
+                Technical::Rsi {
+                    period: params.period,
+                },
+                context,
+            );
+        });
+        registry.register("atr", |params, context| {
+            let params: PeriodParams = parse_params(params)?;
+            return technical(
+                Technical::Atr {
+                    period: params.period,
+                },
+                context,
+            );
+        });
+        registry.register("macd", |params, context| {
+            let params: MacdParams = parse_params(params)?;
+            ensure!(
+                params.fast < params.slow,
+                "macd fast period must be below slow period"
+            );
+            return technical(
+                Technical::Macd {
+                    fast: params.fast,
+                    slow: params.slow,
+                    signal: params.signal,
+                },
+                context,
+            );
+        });
+        registry.register("bollinger", |params, context| {
+            let params: BollingerParams = parse_params(params)?;
+            return technical(
+                Technical::Bollinger {
+                    period: params.period,
+                    std_dev: params.std_dev,
+                },
+                context,
+            );
+        });
+
+        return registry;
+    }
+}
+
+fn technical(
+    technical: Technical,
+    context: IndicatorContext,
+) -> anyhow::Result<Box<dyn CandleCloseIndicator>> {
+    let periods = match technical {
+        Technical::Sma { period }
+        | Technical::Ema { period }
+        | Technical::Rsi { period }
+        | Technical::Atr { period }
+        | Technical::Bollinger { period, .. } => vec![period],
+        Technical::Macd { fast, slow, signal } => vec![fast, slow, signal],
+    };
+    ensure!(
+        periods.iter().all(|x| *x > 0),
+        "{technical} periods must be positive"
+    );
+
+    return Ok(Box::new(TechnicalIndicator::new(
+        technical,
+        context.redis_pool,
+        context.pg_pool,
+        context.is_backtest,
+        context.publish,
+    )));
+}
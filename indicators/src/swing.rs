@@ -4,16 +4,28 @@ use anyhow::Context;
 use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
 use models::{
     schema::{candles, swings},
-    Candle, swing::{SwingBuilder, Swing},
+    swing::{Swing, SwingBuilder},
+    Candle,
 };
 use redis::Commands;
+use rust_decimal::Decimal;
 
 use crate::candle_close::CandleCloseIndicator;
 
+/// Detects N-bar fractal swings: a pivot candle with `left` candles before it
+/// and `right` candles after it that do not reach its high (or low).
+///
+/// Highs (or lows) within `tolerance_percent` of the pivot are treated as
+/// equal. An equal high on the left prevents the swing, an equal high on the
+/// right does not, so the first of equal highs becomes the pivot.
 pub struct SwingIndicator {
     redis_pool: r2d2::Pool<redis::Client>,
     pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
     is_backtest: bool,
+    publish: bool,
+    left: usize,
+    right: usize,
+    tolerance_percent: Decimal,
 }
 
 impl SwingIndicator {
@@ -21,20 +33,29 @@ impl SwingIndicator {
         redis_pool: r2d2::Pool<redis::Client>,
         pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
         is_backtest: bool,
+        publish: bool,
+        left: usize,
+        right: usize,
+        tolerance_percent: Decimal,
     ) -> Self {
         return Self {
             redis_pool,
             pg_pool,
             is_backtest,
+            publish,
+            left,
+            right,
+            tolerance_percent,
         };
     }
 
+    /// The `left + right` candles before `candle` and `candle`, oldest first.
     fn get_last_candles(
         &self,
         candle: &Candle,
         pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
     ) -> anyhow::Result<Vec<Candle>> {
-        let candle = candles::table
+        let mut candles: Vec<Candle> = candles::table
             .filter(
                 candles::pair
                     .eq(candle.pair())
@@ -43,11 +64,12 @@ impl SwingIndicator {
             )
             .select(Candle::as_select())
             .order(candles::open_time.desc())
-            // .limit(4)
-            .limit(2)
+            .limit((self.left + self.right) as i64)
             .get_results(pg_conn)?;
+        candles.reverse();
+        candles.push(candle.to_owned());
 
-        return Ok(candle);
+        return Ok(candles);
     }
 
     fn handle_swing_creation(
@@ -56,45 +78,23 @@ impl SwingIndicator {
         pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
     ) -> anyhow::Result<Option<Swing>> {
         let last_candles = self.get_last_candles(candle, pg_conn)?;
-        // if last_candles.len() != 4 {
-        if last_candles.len() != 2 {
-            println!("wef: {candle:#?} {last_candles:#?}");
-            return Ok(None);
-        }
-        // let (first, second, third, fourth, fifth) = (&last_candles[3], &last_candles[2], &last_candles[1], &last_candles[0], candle);
-        let (first, second, third) = (&last_candles[1], &last_candles[0], candle);
-
-        let mut swing_builder = SwingBuilder::default();
-        // if third.low() < first.low() && third.low() < second.low() && third.low() < fourth.low() && third.low() < fifth.low() {
-        //     swing_builder
-        //         .price(third.low().to_owned())
-        //         .flow("bull".to_owned());
-        // } else if third.high() > first.high() && third.high() > second.high() && third.high() > fourth.high() && third.high() > fifth.high() {
-        //     swing_builder
-        //         .price(third.high().to_owned())
-        //         .flow("bear".to_owned());
-        // } else {
-        //     return Ok(None);
-        // }
-        if second.low() < first.low() && second.low() < third.low() {
-            swing_builder
-                .price(second.low().to_owned())
-                .flow("bull".to_owned());
-        } else if second.high() > first.high() && second.high() > third.high() {
-            swing_builder
-                .price(second.high().to_owned())
-                .flow("bear".to_owned());
-        } else {
+        if last_candles.len() != self.left + self.right + 1 {
             return Ok(None);
         }
+        let (price, flow) = match find_pivot(&last_candles, self.left, self.tolerance_percent) {
+            Some(pivot) => pivot,
+            None => return Ok(None),
+        };
 
-        swing_builder
+        let swing = SwingBuilder::default()
             .pair(candle.pair().to_owned())
-            // .open_time(third.open_time().to_owned())
-            .open_time(second.open_time().to_owned())
+            .open_time(last_candles[self.left].open_time().to_owned())
             .timeframe(candle.timeframe().to_owned())
-            .close_time(None);
-        let swing = swing_builder.build()?;
+            .price(price)
+            .flow(flow.to_owned())
+            .close_time(None)
+            .confirmation_time(Some(candle.close_time()))
+            .build()?;
         let result: Swing = diesel::insert_into(swings::table)
             .values(swing)
             .get_result(pg_conn)?;
@@ -116,7 +116,9 @@ impl SwingIndicator {
                         swings::flow
                             .eq("bull")
                             .and(swings::price.gt(candle.close()))
-                            .or(swings::flow.eq("bear").and(swings::price.lt(candle.close()))),
+                            .or(swings::flow
+                                .eq("bear")
+                                .and(swings::price.lt(candle.close()))),
                     )
                     .and(swings::close_time.is_null()),
             ),
@@ -127,11 +129,10 @@ impl SwingIndicator {
         return Ok(swings);
     }
 
-    fn publish_swings(
-        &self,
-        swings: Vec<Swing>,
-        channel: Cow<'static, str>,
-    ) -> anyhow::Result<()> {
+    fn publish_swings(&self, swings: Vec<Swing>, channel: Cow<'static, str>) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
         let redis_conn = &mut self
             .redis_pool
             .get()
@@ -142,7 +143,7 @@ impl SwingIndicator {
             channel
         };
         for swing in swings.iter() {
-            redis_conn
+            let _: () = redis_conn
                 .publish(
                     channel.to_string(),
                     serde_json::to_string(swing).context(format!(
@@ -164,11 +165,98 @@ impl CandleCloseIndicator for SwingIndicator {
         let new_swing = self.handle_swing_creation(candle, pg_conn)?;
         let closed_swings = self.handle_closed_swings(candle, pg_conn)?;
 
-        // println!("new_swing: {new_swing:#?} closed_swings: {closed_swings:#?}");
         if let Some(new_swing) = new_swing {
-            self.publish_swings(vec![new_swing], Cow::Borrowed("swing")).context("publishing new_swing")?;
+            self.publish_swings(vec![new_swing], Cow::Borrowed("swing"))
+                .context("publishing new_swing")?;
         }
-        self.publish_swings(closed_swings, Cow::Borrowed("swing_close")).context("publishing closed_swings")?;
+        self.publish_swings(closed_swings, Cow::Borrowed("swing_close"))
+            .context("publishing closed_swings")?;
         return Ok(());
     }
 }
+
+/// Price and flow of the swing formed by `candles[left]`, when there is one.
+///
+/// A low pivot takes precedence over a high pivot on the same candle.
+fn find_pivot(
+    candles: &[Candle],
+    left: usize,
+    tolerance_percent: Decimal,
+) -> Option<(Decimal, &'static str)> {
+    let pivot = &candles[left];
+    let (before, after) = (&candles[..left], &candles[left + 1..]);
+
+    let low = *pivot.low();
+    let tolerance = low * tolerance_percent / Decimal::ONE_HUNDRED;
+    if before.iter().all(|x| *x.low() > low + tolerance)
+        && after.iter().all(|x| *x.low() >= low - tolerance)
+    {
+        return Some((low, "bull"));
+    }
+
+    let high = *pivot.high();
+    let tolerance = high * tolerance_percent / Decimal::ONE_HUNDRED;
+    if before.iter().all(|x| *x.high() < high - tolerance)
+        && after.iter().all(|x| *x.high() <= high + tolerance)
+    {
+        return Some((high, "bear"));
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{candle::CandleBuilder, Candle};
+    use rust_decimal::Decimal;
+
+    use super::find_pivot;
+
+    fn candle(high: i64, low: i64) -> Candle {
+        return CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(chrono::Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(low))
+            .high(Decimal::from(high))
+            .low(Decimal::from(low))
+            .close(Decimal::from(high))
+            .size_in_millis(60_000)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn pivots_with_tolerance() {
+        let candles = [
+            candle(10, 5),
+            candle(12, 6),
+            candle(20, 7),
+            candle(15, 8),
+            candle(11, 6),
+        ];
+        assert_eq!(
+            find_pivot(&candles, 2, Decimal::ZERO),
+            Some((Decimal::from(20), "bear"))
+        );
+
+        // The second of equal highs is not a pivot, the first one is.
+        let candles = [candle(100, 5), candle(101, 6), candle(90, 7)];
+        assert_eq!(
+            find_pivot(&candles, 1, Decimal::ZERO),
+            Some((Decimal::from(101), "bear"))
+        );
+        assert_eq!(find_pivot(&candles, 1, Decimal::TWO), None);
+        let candles = [candle(90, 5), candle(101, 6), candle(100, 7)];
+        assert_eq!(
+            find_pivot(&candles, 1, Decimal::TWO),
+            Some((Decimal::from(101), "bear"))
+        );
+
+        let candles = [candle(10, 5), candle(10, 3), candle(10, 3)];
+        assert_eq!(
+            find_pivot(&candles, 1, Decimal::ZERO),
+            Some((Decimal::from(3), "bull"))
+        );
+    }
+}
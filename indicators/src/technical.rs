@@ -0,0 +1,456 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::Context;
+use diesel::{prelude::*, r2d2::ConnectionManager, upsert::excluded, PgConnection};
+use models::{
+    indicator_value::{IndicatorValue, IndicatorValueBuilder},
+    schema::{candles, indicator_values},
+    Candle,
+};
+use redis::Commands;
+use rust_decimal::{Decimal, MathematicalOps};
+
+use crate::candle_close::CandleCloseIndicator;
+
+/// Classic indicators computed on candle closes.
+#[derive(Debug, Clone)]
+pub enum Technical {
+    Sma {
+        period: usize,
+    },
+    Ema {
+        period: usize,
+    },
+    Rsi {
+        period: usize,
+    },
+    Atr {
+        period: usize,
+    },
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        std_dev: Decimal,
+    },
+}
+
+impl std::fmt::Display for Technical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Technical::Sma { period } => write!(f, "sma-{period}"),
+            Technical::Ema { period } => write!(f, "ema-{period}"),
+            Technical::Rsi { period } => write!(f, "rsi-{period}"),
+            Technical::Atr { period } => write!(f, "atr-{period}"),
+            Technical::Macd { fast, slow, signal } => write!(f, "macd-{fast}-{slow}-{signal}"),
+            Technical::Bollinger { period, std_dev } => write!(f, "bollinger-{period}-{std_dev}"),
+        };
+    }
+}
+
+/// Updates a classic indicator incrementally on every candle close, from its
+/// values on the previous candle, and stores every output in `indicator_values`.
+///
+/// Moving averages are seeded with the simple average of their first `period`
+/// values, and RSI / ATR use Wilder's smoothing.
+pub struct TechnicalIndicator {
+    technical: Technical,
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+}
+
+impl TechnicalIndicator {
+    pub fn new(
+        technical: Technical,
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+    ) -> Self {
+        return Self {
+            technical,
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+        };
+    }
+
+    /// The last `count` candles up to `candle` included, oldest first.
+    fn get_last_candles(
+        &self,
+        candle: &Candle,
+        count: usize,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let mut candles: Vec<Candle> = candles::table
+            .filter(
+                candles::pair
+                    .eq(candle.pair())
+                    .and(candles::timeframe.eq(candle.timeframe()))
+                    .and(candles::open_time.le(candle.open_time())),
+            )
+            .select(Candle::as_select())
+            .order(candles::open_time.desc())
+            .limit(i64::try_from(count)?)
+            .get_results(pg_conn)?;
+        candles.reverse();
+
+        return Ok(candles);
+    }
+
+    /// The last `count` values of `output` before `candle`, oldest first.
+    fn get_last_values(
+        &self,
+        candle: &Candle,
+        output: &str,
+        count: usize,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<Decimal>> {
+        let mut values: Vec<Decimal> = indicator_values::table
+            .filter(
+                indicator_values::pair
+                    .eq(candle.pair())
+                    .and(indicator_values::timeframe.eq(candle.timeframe()))
+                    .and(indicator_values::indicator.eq(self.technical.to_string()))
+                    .and(indicator_values::output.eq(output))
+                    .and(indicator_values::open_time.lt(candle.open_time())),
+            )
+            .select(indicator_values::value)
+            .order(indicator_values::open_time.desc())
+            .limit(i64::try_from(count)?)
+            .get_results(pg_conn)?;
+        values.reverse();
+
+        return Ok(values);
+    }
+
+    /// Every output of the indicator on the candle before `candle`.
+    fn get_previous_outputs(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<HashMap<String, Decimal>> {
+        let previous_open_time: Option<chrono::DateTime<chrono::Utc>> = indicator_values::table
+            .filter(
+                indicator_values::pair
+                    .eq(candle.pair())
+                    .and(indicator_values::timeframe.eq(candle.timeframe()))
+                    .and(indicator_values::indicator.eq(self.technical.to_string()))
+                    .and(indicator_values::open_time.lt(candle.open_time())),
+            )
+            .select(indicator_values::open_time)
+            .order(indicator_values::open_time.desc())
+            .first(pg_conn)
+            .optional()?;
+        let previous_open_time = match previous_open_time {
+            Some(x) => x,
+            None => return Ok(HashMap::new()),
+        };
+        let outputs: Vec<(String, Decimal)> = indicator_values::table
+            .filter(
+                indicator_values::pair
+                    .eq(candle.pair())
+                    .and(indicator_values::timeframe.eq(candle.timeframe()))
+                    .and(indicator_values::indicator.eq(self.technical.to_string()))
+                    .and(indicator_values::open_time.eq(previous_open_time)),
+            )
+            .select((indicator_values::output, indicator_values::value))
+            .get_results(pg_conn)?;
+
+        return Ok(outputs.into_iter().collect());
+    }
+
+    /// Outputs of the indicator for `candle`, empty while there is not enough history.
+    fn compute(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut r2d2::PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> anyhow::Result<Vec<(&'static str, Decimal)>> {
+        let previous = self.get_previous_outputs(candle, pg_conn)?;
+        let closes = |candles: &[Candle]| -> Vec<Decimal> {
+            return candles.iter().map(|x| x.close().to_owned()).collect();
+        };
+
+        let outputs = match self.technical {
+            Technical::Sma { period } => {
+                let candles = self.get_last_candles(candle, period, pg_conn)?;
+                if candles.len() < period {
+                    return Ok(Vec::new());
+                }
+                vec![("sma", mean(&closes(&candles)))]
+            }
+            Technical::Ema { period } => {
+                let count = if previous.contains_key("ema") {
+                    1
+                } else {
+                    period
+                };
+                let candles = self.get_last_candles(candle, count, pg_conn)?;
+                match ema(previous.get("ema"), &closes(&candles), period) {
+                    Some(ema) => vec![("ema", ema)],
+                    None => Vec::new(),
+                }
+            }
+            Technical::Rsi { period } => {
+                let count = if previous.contains_key("avg_gain") {
+                    2
+                } else {
+                    period + 1
+                };
+                let candles = self.get_last_candles(candle, count, pg_conn)?;
+                if candles.len() < count {
+                    return Ok(Vec::new());
+                }
+                let changes: Vec<Decimal> = candles
+                    .windows(2)
+                    .map(|x| x[1].close() - x[0].close())
+                    .collect();
+                let gains: Vec<Decimal> = changes
+                    .iter()
+                    .map(|x| x.max(&Decimal::ZERO).to_owned())
+                    .collect();
+                let losses: Vec<Decimal> =
+                    changes.iter().map(|x| (-x).max(Decimal::ZERO)).collect();
+                let (avg_gain, avg_loss) =
+                    match (previous.get("avg_gain"), previous.get("avg_loss")) {
+                        (Some(avg_gain), Some(avg_loss)) => (
+                            wilder(*avg_gain, gains[0], period),
+                            wilder(*avg_loss, losses[0], period),
+                        ),
+                        _ => (mean(&gains), mean(&losses)),
+                    };
+                vec![
+                    ("avg_gain", avg_gain),
+                    ("avg_loss", avg_loss),
+                    ("rsi", rsi(avg_gain, avg_loss)),
+                ]
+            }
+            Technical::Atr { period } => {
+                let count = if previous.contains_key("atr") {
+                    2
+                } else {
+                    period + 1
+                };
+                let candles = self.get_last_candles(candle, count, pg_conn)?;
+                if candles.len() < count {
+                    return Ok(Vec::new());
+                }
+                let true_ranges: Vec<Decimal> = candles
+                    .windows(2)
+                    .map(|x| true_range(&x[1], *x[0].close()))
+                    .collect();
+                let atr = match previous.get("atr") {
+                    Some(atr) => wilder(*atr, true_ranges[0], period),
+                    None => mean(&true_ranges),
+                };
+                vec![("atr", atr)]
+            }
+            Technical::Macd { fast, slow, signal } => {
+                let count = match (
+                    previous.contains_key("fast_ema"),
+                    previous.contains_key("slow_ema"),
+                ) {
+                    (true, true) => 1,
+                    _ => fast.max(slow),
+                };
+                let candles = self.get_last_candles(candle, count, pg_conn)?;
+                let fast_closes = closes(&candles[candles.len().saturating_sub(fast)..]);
+                let fast_ema = ema(previous.get("fast_ema"), &fast_closes, fast);
+                let slow_ema = ema(previous.get("slow_ema"), &closes(&candles), slow);
+                let mut outputs = Vec::new();
+                outputs.extend(fast_ema.map(|x| ("fast_ema", x)));
+                outputs.extend(slow_ema.map(|x| ("slow_ema", x)));
+                if let (Some(fast_ema), Some(slow_ema)) = (fast_ema, slow_ema) {
+                    let macd = fast_ema - slow_ema;
+                    let signal_ema = match previous.get("signal") {
+                        Some(previous) => Some(ema_step(*previous, macd, signal)),
+                        None => {
+                            let mut macds =
+                                self.get_last_values(candle, "macd", signal - 1, pg_conn)?;
+                            macds.push(macd);
+                            ema(None, &macds, signal)
+                        }
+                    };
+                    outputs.push(("macd", macd));
+                    if let Some(signal_ema) = signal_ema {
+                        outputs.push(("signal", signal_ema));
+                        outputs.push(("histogram", macd - signal_ema));
+                    }
+                }
+                outputs
+            }
+            Technical::Bollinger { period, std_dev } => {
+                let candles = self.get_last_candles(candle, period, pg_conn)?;
+                if candles.len() < period {
+                    return Ok(Vec::new());
+                }
+                let closes = closes(&candles);
+                let middle = mean(&closes);
+                let width = standard_deviation(&closes) * std_dev;
+                vec![
+                    ("middle", middle),
+                    ("upper", middle + width),
+                    ("lower", middle - width),
+                ]
+            }
+        };
+        return Ok(outputs);
+    }
+
+    fn publish_values(&self, values: &[IndicatorValue]) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel: Cow<'static, str> = if self.is_backtest {
+            Cow::Borrowed("backtest-indicator")
+        } else {
+            Cow::Borrowed("indicator")
+        };
+        for value in values.iter() {
+            let _: () = redis_conn
+                .publish(
+                    channel.to_string(),
+                    serde_json::to_string(value).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for TechnicalIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let values = self
+            .compute(candle, pg_conn)?
+            .into_iter()
+            .map(|(output, value)| {
+                return IndicatorValueBuilder::default()
+                    .pair(candle.pair().to_owned())
+                    .open_time(candle.open_time().to_owned())
+                    .timeframe(candle.timeframe().to_owned())
+                    .indicator(self.technical.to_string())
+                    .output(output.to_owned())
+                    .value(value)
+                    .build();
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        diesel::insert_into(indicator_values::table)
+            .values(&values)
+            .on_conflict((
+                indicator_values::pair,
+                indicator_values::open_time,
+                indicator_values::timeframe,
+                indicator_values::indicator,
+                indicator_values::output,
+            ))
+            .do_update()
+            .set(indicator_values::value.eq(excluded(indicator_values::value)))
+            .execute(pg_conn)?;
+        self.publish_values(&values)
+            .context(format!("publishing {} values", self.technical))?;
+        return Ok(());
+    }
+}
+
+fn mean(values: &[Decimal]) -> Decimal {
+    if values.is_empty() {
+        return Decimal::ZERO;
+    }
+    return values.iter().sum::<Decimal>() / Decimal::from(values.len());
+}
+
+/// Population standard deviation.
+fn standard_deviation(values: &[Decimal]) -> Decimal {
+    let average = mean(values);
+    let squares: Vec<Decimal> = values
+        .iter()
+        .map(|x| (x - average) * (x - average))
+        .collect();
+    return mean(&squares).sqrt().unwrap_or_default();
+}
+
+fn ema_step(previous: Decimal, value: Decimal, period: usize) -> Decimal {
+    let k = Decimal::TWO / Decimal::from(period + 1);
+    return previous + k * (value - previous);
+}
+
+/// EMA of the last value of `values` from the previous EMA, or seeded with the
+/// average of `values` once they cover `period`.
+fn ema(previous: Option<&Decimal>, values: &[Decimal], period: usize) -> Option<Decimal> {
+    return match (previous, values.last()) {
+        (Some(previous), Some(value)) => Some(ema_step(*previous, *value, period)),
+        (None, _) if values.len() >= period => Some(mean(&values[values.len() - period..])),
+        _ => None,
+    };
+}
+
+/// Wilder's smoothing of `value` into `previous`.
+fn wilder(previous: Decimal, value: Decimal, period: usize) -> Decimal {
+    let period = Decimal::from(period);
+    return (previous * (period - Decimal::ONE) + value) / period;
+}
+
+fn rsi(avg_gain: Decimal, avg_loss: Decimal) -> Decimal {
+    if avg_loss.is_zero() {
+        return Decimal::ONE_HUNDRED;
+    }
+    return Decimal::ONE_HUNDRED - Decimal::ONE_HUNDRED / (Decimal::ONE + avg_gain / avg_loss);
+}
+
+fn true_range(candle: &Candle, previous_close: Decimal) -> Decimal {
+    return (candle.high() - candle.low())
+        .max((candle.high() - previous_close).abs())
+        .max((candle.low() - previous_close).abs());
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{ema, ema_step, rsi, standard_deviation, wilder};
+
+    #[test]
+    fn moving_averages_and_oscillators() {
+        let values: Vec<Decimal> = [2, 4, 4, 4, 5, 5, 7, 9]
+            .iter()
+            .map(|x| Decimal::from(*x))
+            .collect();
+
+        assert_eq!(ema(None, &values[..2], 3), None);
+        assert_eq!(
+            ema(None, &values[..3], 3),
+            Some(Decimal::from(10) / Decimal::from(3))
+        );
+        assert_eq!(
+            ema_step(Decimal::from(4), Decimal::from(6), 3),
+            Decimal::from(5)
+        );
+        assert_eq!(
+            wilder(Decimal::from(2), Decimal::from(6), 3),
+            Decimal::from(10) / Decimal::from(3)
+        );
+        assert_eq!(standard_deviation(&values), Decimal::from(2));
+        assert_eq!(rsi(Decimal::ONE, Decimal::ZERO), Decimal::ONE_HUNDRED);
+        assert_eq!(rsi(Decimal::ONE, Decimal::ONE), Decimal::from(50));
+    }
+}
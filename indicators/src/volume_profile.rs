@@ -0,0 +1,427 @@
+use anyhow::Context;
+use chrono_tz::Tz;
+use diesel::{prelude::*, r2d2::ConnectionManager, upsert::excluded, PgConnection};
+use models::{
+    schema::{volume_profile_bins, volume_profiles},
+    volume_profile::{
+        VolumeProfile, VolumeProfileBin, VolumeProfileBinBuilder, VolumeProfileBuilder,
+    },
+    Candle,
+};
+use redis::Commands;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    candle_close::CandleCloseIndicator,
+    levels::{LevelsParams, Period, Session},
+};
+
+/// Fixed window of time to keep a volume profile of.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixedRange {
+    pub name: String,
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+}
+
+/// Windows to keep volume profiles of, and how to bin and analyze them.
+/// Periods start at midnight in `time_zone`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeProfileParams {
+    /// Price range of a bin.
+    pub bin_size: Decimal,
+    #[serde(default = "VolumeProfileParams::default_periods")]
+    pub periods: Vec<Period>,
+    #[serde(default = "VolumeProfileParams::default_time_zone")]
+    pub time_zone: Tz,
+    #[serde(default = "LevelsParams::default_sessions")]
+    pub sessions: Vec<Session>,
+    #[serde(default)]
+    pub ranges: Vec<FixedRange>,
+    #[serde(default = "VolumeProfileParams::default_value_area_percent")]
+    pub value_area_percent: Decimal,
+    /// Percent of the POC volume below which a bin can be a low volume node.
+    #[serde(default = "VolumeProfileParams::default_low_volume_percent")]
+    pub low_volume_percent: Decimal,
+}
+
+impl VolumeProfileParams {
+    fn default_periods() -> Vec<Period> {
+        return vec![Period::Day, Period::Week];
+    }
+
+    fn default_time_zone() -> Tz {
+        return Tz::UTC;
+    }
+
+    fn default_value_area_percent() -> Decimal {
+        return Decimal::from(70);
+    }
+
+    fn default_low_volume_percent() -> Decimal {
+        return Decimal::from(25);
+    }
+
+    /// Name, start and end of every period, session and range containing `time`.
+    pub fn windows(
+        &self,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<(
+        &str,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+    )> {
+        let periods = self.periods.iter().filter_map(|x| {
+            let (start, end) = x.window(&self.time_zone, time)?;
+            return Some((x.name(), start, end));
+        });
+        let sessions = self.sessions.iter().filter_map(|x| {
+            let (start, end) = x.window(time)?;
+            return Some((x.name.as_str(), start, end));
+        });
+        let ranges = self
+            .ranges
+            .iter()
+            .filter(|x| x.start <= time && time < x.end)
+            .map(|x| (x.name.as_str(), x.start, x.end));
+
+        return periods.chain(sessions).chain(ranges).collect();
+    }
+}
+
+/// Completed volume profile with its bins, published on `volume_profile`.
+#[derive(Serialize)]
+struct VolumeProfileEvent {
+    #[serde(flatten)]
+    profile: VolumeProfile,
+    bins: Vec<VolumeProfileBin>,
+}
+
+/// Bins the volume of the candles of a pair by price over periods, sessions
+/// and fixed ranges, stored in `volume_profiles` and `volume_profile_bins`.
+///
+/// Every candle updates the POC, value area and low volume nodes of the
+/// profiles of the windows it opened in, so the indicator should run on a
+/// single timeframe dividing every session, e.g. `1m`. Completed profiles are
+/// published on `volume_profile`.
+pub struct VolumeProfileIndicator {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    publish: bool,
+    params: VolumeProfileParams,
+}
+
+impl VolumeProfileIndicator {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        publish: bool,
+        params: VolumeProfileParams,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            publish,
+            params,
+        };
+    }
+
+    fn upsert_profile(
+        &self,
+        profile: &VolumeProfile,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<()> {
+        diesel::insert_into(volume_profiles::table)
+            .values(profile)
+            .on_conflict((
+                volume_profiles::pair,
+                volume_profiles::open_time,
+                volume_profiles::name,
+            ))
+            .do_update()
+            .set(profile)
+            .execute(pg_conn)?;
+        return Ok(());
+    }
+
+    fn get_bins(
+        &self,
+        profile: &VolumeProfile,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<Vec<VolumeProfileBin>> {
+        let bins = volume_profile_bins::table
+            .filter(
+                volume_profile_bins::pair
+                    .eq(profile.pair())
+                    .and(volume_profile_bins::open_time.eq(profile.open_time()))
+                    .and(volume_profile_bins::name.eq(profile.name())),
+            )
+            .select(VolumeProfileBin::as_select())
+            .order(volume_profile_bins::price.asc())
+            .get_results(pg_conn)?;
+
+        return Ok(bins);
+    }
+
+    /// Adds the volume of `candle` to `profile` and analyzes it again.
+    fn add_candle(
+        &self,
+        profile: &mut VolumeProfile,
+        candle: &Candle,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<()> {
+        let added: Vec<VolumeProfileBin> = profile
+            .distribute(candle)
+            .into_iter()
+            .map(|(price, volume)| {
+                return VolumeProfileBinBuilder::default()
+                    .pair(profile.pair().to_owned())
+                    .open_time(profile.open_time().to_owned())
+                    .name(profile.name().to_owned())
+                    .price(price)
+                    .volume(volume)
+                    .build();
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !added.is_empty() {
+            diesel::insert_into(volume_profile_bins::table)
+                .values(&added)
+                .on_conflict((
+                    volume_profile_bins::pair,
+                    volume_profile_bins::open_time,
+                    volume_profile_bins::name,
+                    volume_profile_bins::price,
+                ))
+                .do_update()
+                .set(
+                    volume_profile_bins::volume
+                        .eq(volume_profile_bins::volume + excluded(volume_profile_bins::volume)),
+                )
+                .execute(pg_conn)?;
+        }
+
+        profile.extend(candle);
+        let mut bins = self.get_bins(profile, pg_conn)?;
+        let previous: Vec<bool> = bins.iter().map(|x| *x.low_volume_node()).collect();
+        profile.analyze(
+            &mut bins,
+            self.params.value_area_percent,
+            self.params.low_volume_percent,
+        );
+        for (bin, previous) in bins.iter().zip(previous) {
+            if *bin.low_volume_node() == previous {
+                continue;
+            }
+            diesel::update(volume_profile_bins::table.find((
+                bin.pair(),
+                bin.open_time(),
+                bin.name(),
+                bin.price(),
+            )))
+            .set(volume_profile_bins::low_volume_node.eq(bin.low_volume_node()))
+            .execute(pg_conn)?;
+        }
+        return self.upsert_profile(profile, pg_conn);
+    }
+
+    /// Adds `candle` to the profiles of the windows it opened in, returning the
+    /// ones it completed.
+    fn handle_profile_updates(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<Vec<VolumeProfile>> {
+        let mut completed = Vec::new();
+        for (name, start, end) in self.params.windows(*candle.open_time()) {
+            let profile = volume_profiles::table
+                .find((candle.pair(), start, name))
+                .select(VolumeProfile::as_select())
+                .first(pg_conn)
+                .optional()?;
+            let mut profile = match profile {
+                Some(profile)
+                    if *profile.complete() || profile.updated_time() >= candle.open_time() =>
+                {
+                    continue
+                }
+                Some(profile) => profile,
+                None => VolumeProfileBuilder::default()
+                    .pair(candle.pair().to_owned())
+                    .open_time(start)
+                    .name(name.to_owned())
+                    .close_time(end)
+                    .bin_size(self.params.bin_size)
+                    .updated_time(start - chrono::Duration::milliseconds(1))
+                    .build()?,
+            };
+            // Bins and profile are updated together, so a candle is never added twice.
+            pg_conn.transaction(|pg_conn| self.add_candle(&mut profile, candle, pg_conn))?;
+            if *profile.complete() {
+                completed.push(profile);
+            }
+        }
+        return Ok(completed);
+    }
+
+    /// Completes the profiles whose window ended without a candle closing it.
+    fn handle_ended_profiles(
+        &self,
+        candle: &Candle,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<Vec<VolumeProfile>> {
+        let profiles: Vec<VolumeProfile> = volume_profiles::table
+            .filter(
+                volume_profiles::pair
+                    .eq(candle.pair())
+                    .and(volume_profiles::close_time.le(candle.open_time()))
+                    .and(volume_profiles::complete.eq(false)),
+            )
+            .select(VolumeProfile::as_select())
+            .get_results(pg_conn)?;
+
+        let mut completed = Vec::new();
+        for mut profile in profiles.into_iter() {
+            if profile.complete_by(candle) {
+                self.upsert_profile(&profile, pg_conn)?;
+                completed.push(profile);
+            }
+        }
+        return Ok(completed);
+    }
+
+    fn publish_profiles(
+        &self,
+        profiles: Vec<VolumeProfile>,
+        pg_conn: &mut PgConnection,
+    ) -> anyhow::Result<()> {
+        if !self.publish {
+            return Ok(());
+        }
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = match self.is_backtest {
+            false => "volume_profile",
+            true => "backtest-volume_profile",
+        };
+        for profile in profiles.into_iter() {
+            let bins = self.get_bins(&profile, pg_conn)?;
+            let event = VolumeProfileEvent { profile, bins };
+            let _: () = redis_conn
+                .publish(
+                    channel,
+                    serde_json::to_string(&event).context(format!(
+                        "Stringify result for publishing on redis {channel}"
+                    ))?,
+                )
+                .context(format!("Publishing to redis {channel} channel"))?;
+        }
+        return Ok(());
+    }
+}
+
+impl CandleCloseIndicator for VolumeProfileIndicator {
+    fn process(&self, candle: &Candle) -> anyhow::Result<()> {
+        let pg_conn = &mut self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool")?;
+        let mut completed = self.handle_ended_profiles(candle, pg_conn)?;
+        completed.extend(self.handle_profile_updates(candle, pg_conn)?);
+
+        self.publish_profiles(completed, pg_conn)
+            .context("publishing completed volume profiles")?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use models::{
+        candle::CandleBuilder,
+        volume_profile::{VolumeProfileBin, VolumeProfileBinBuilder, VolumeProfileBuilder},
+    };
+    use rust_decimal::Decimal;
+
+    use super::VolumeProfileParams;
+
+    #[test]
+    fn poc_value_area_and_low_volume_nodes() {
+        let time = chrono::Utc.with_ymd_and_hms(2024, 7, 2, 0, 0, 0).unwrap();
+        let mut profile = VolumeProfileBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time)
+            .name("day".to_owned())
+            .close_time(time + chrono::Duration::days(1))
+            .bin_size(Decimal::from(10))
+            .updated_time(time)
+            .build()
+            .unwrap();
+
+        let candle = CandleBuilder::default()
+            .pair("BTC-USD".to_owned())
+            .open_time(time)
+            .timeframe("1m".to_owned())
+            .open(Decimal::from(105))
+            .high(Decimal::from(125))
+            .low(Decimal::from(105))
+            .close(Decimal::from(125))
+            .size_in_millis(60_000)
+            .volume(Decimal::from(8))
+            .build()
+            .unwrap();
+        assert_eq!(
+            profile.distribute(&candle),
+            vec![
+                (Decimal::from(100), Decimal::from(2)),
+                (Decimal::from(110), Decimal::from(4)),
+                (Decimal::from(120), Decimal::from(2)),
+            ]
+        );
+
+        let bin = |price: i64, volume: i64| -> VolumeProfileBin {
+            return VolumeProfileBinBuilder::default()
+                .pair("BTC-USD".to_owned())
+                .open_time(time)
+                .name("day".to_owned())
+                .price(Decimal::from(price))
+                .volume(Decimal::from(volume))
+                .build()
+                .unwrap();
+        };
+        let mut bins = vec![
+            bin(100, 10),
+            bin(110, 2),
+            bin(120, 30),
+            bin(130, 40),
+            bin(140, 15),
+            bin(150, 3),
+        ];
+        profile.analyze(&mut bins, Decimal::from(70), Decimal::from(25));
+        assert_eq!(*profile.poc(), Some(Decimal::from(135)));
+        // 40 + 30 reaches 70 of the 100 traded.
+        assert_eq!(*profile.value_area_low(), Some(Decimal::from(120)));
+        assert_eq!(*profile.value_area_high(), Some(Decimal::from(140)));
+        let nodes: Vec<bool> = bins.iter().map(|x| *x.low_volume_node()).collect();
+        assert_eq!(nodes, vec![false, true, false, false, false, false]);
+
+        let params: VolumeProfileParams = toml::from_str(
+            r#"
+            bin_size = 10
+            sessions = []
+            ranges = [{ name = "fomc", start = "2024-07-01T18:00:00Z", end = "2024-07-03T18:00:00Z" }]
+            "#,
+        )
+        .unwrap();
+        let names: Vec<&str> = params.windows(time).iter().map(|x| x.0).collect();
+        assert_eq!(names, vec!["day", "week", "fomc"]);
+    }
+}
@@ -0,0 +1 @@
+alter table candles add column synthetic boolean not null default false;
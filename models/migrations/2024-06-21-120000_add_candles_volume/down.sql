@@ -0,0 +1,5 @@
+alter table candles
+    drop column volume,
+    drop column quote_volume,
+    drop column trade_count,
+    drop column vwap;
@@ -0,0 +1,5 @@
+alter table candles
+    add column volume decimal not null default 0,
+    add column quote_volume decimal not null default 0,
+    add column trade_count bigint not null default 0,
+    add column vwap decimal default null;
@@ -0,0 +1 @@
+alter table candles add column backfilled boolean not null default false;
@@ -0,0 +1,2 @@
+alter table candles drop column last_sequence;
+alter table candles drop column last_tick_time;
@@ -0,0 +1,2 @@
+alter table candles add column last_tick_time timestamptz;
+alter table candles add column last_sequence bigint;
@@ -0,0 +1 @@
+alter table candles drop column bar_type;
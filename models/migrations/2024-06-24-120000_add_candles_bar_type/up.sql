@@ -0,0 +1 @@
+alter table candles add column bar_type text not null default 'time';
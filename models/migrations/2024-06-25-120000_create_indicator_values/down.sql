@@ -0,0 +1 @@
+drop table indicator_values;
@@ -0,0 +1,11 @@
+create table indicator_values (
+    pair text not null,
+    open_time timestamptz not null,
+    timeframe text not null,
+    indicator text not null,
+    output text not null,
+    value decimal not null,
+    primary key (pair, open_time, timeframe, indicator, output)
+);
+
+select create_hypertable('indicator_values', by_range('open_time'));
@@ -0,0 +1 @@
+alter table swings drop column confirmation_time;
@@ -0,0 +1 @@
+alter table swings add column confirmation_time timestamptz;
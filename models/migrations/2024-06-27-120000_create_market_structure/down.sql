@@ -0,0 +1,2 @@
+drop table structure_breaks;
+drop table market_structures;
@@ -0,0 +1,29 @@
+create table market_structures (
+    pair text not null,
+    timeframe text not null,
+    trend text default null,
+    swing_high decimal default null,
+    swing_high_time timestamptz default null,
+    swing_high_label text default null,
+    swing_high_broken boolean not null default false,
+    swing_low decimal default null,
+    swing_low_time timestamptz default null,
+    swing_low_label text default null,
+    swing_low_broken boolean not null default false,
+    updated_time timestamptz not null,
+    primary key (pair, timeframe)
+);
+
+create table structure_breaks (
+    pair text not null,
+    open_time timestamptz not null,
+    timeframe text not null,
+    kind text not null,
+    flow text not null,
+    swing_time timestamptz not null,
+    swing_price decimal not null,
+    close decimal not null,
+    primary key (pair, open_time, timeframe, flow)
+);
+
+select create_hypertable('structure_breaks', by_range('open_time'));
@@ -0,0 +1,13 @@
+create table order_blocks (
+    pair text not null,
+    open_time timestamptz not null,
+    timeframe text not null,
+    high decimal not null,
+    low decimal not null,
+    flow text not null,
+    break_time timestamptz not null,
+    mitigation_time timestamptz default null,
+    primary key (pair, open_time, timeframe)
+);
+
+select create_hypertable('order_blocks', by_range('open_time'));
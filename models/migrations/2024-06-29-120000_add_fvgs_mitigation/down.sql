@@ -0,0 +1,4 @@
+alter table fvgs drop column inversion_time;
+alter table fvgs drop column ce_time;
+alter table fvgs drop column fill_percent;
+alter table fvgs drop column touch_time;
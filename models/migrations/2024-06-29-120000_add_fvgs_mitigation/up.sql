@@ -0,0 +1,4 @@
+alter table fvgs add column touch_time timestamptz default null;
+alter table fvgs add column fill_percent decimal not null default 0;
+alter table fvgs add column ce_time timestamptz default null;
+alter table fvgs add column inversion_time timestamptz default null;
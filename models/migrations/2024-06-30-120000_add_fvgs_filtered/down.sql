@@ -0,0 +1 @@
+alter table fvgs drop column filtered;
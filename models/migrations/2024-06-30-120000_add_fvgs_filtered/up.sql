@@ -0,0 +1 @@
+alter table fvgs add column filtered boolean not null default false;
@@ -0,0 +1,14 @@
+create table levels (
+    pair text not null,
+    open_time timestamptz not null,
+    name text not null,
+    close_time timestamptz not null,
+    high decimal not null,
+    low decimal not null,
+    complete boolean not null default false,
+    high_taken_time timestamptz default null,
+    low_taken_time timestamptz default null,
+    primary key (pair, open_time, name)
+);
+
+select create_hypertable('levels', by_range('open_time'));
@@ -0,0 +1,2 @@
+drop table volume_profile_bins;
+drop table volume_profiles;
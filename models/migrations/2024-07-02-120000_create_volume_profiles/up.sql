@@ -0,0 +1,28 @@
+create table volume_profiles (
+    pair text not null,
+    open_time timestamptz not null,
+    name text not null,
+    close_time timestamptz not null,
+    bin_size decimal not null,
+    volume decimal not null default 0,
+    poc decimal default null,
+    value_area_high decimal default null,
+    value_area_low decimal default null,
+    complete boolean not null default false,
+    updated_time timestamptz not null,
+    primary key (pair, open_time, name)
+);
+
+select create_hypertable('volume_profiles', by_range('open_time'));
+
+create table volume_profile_bins (
+    pair text not null,
+    open_time timestamptz not null,
+    name text not null,
+    price decimal not null,
+    volume decimal not null,
+    low_volume_node boolean not null default false,
+    primary key (pair, open_time, name, price)
+);
+
+select create_hypertable('volume_profile_bins', by_range('open_time'));
@@ -0,0 +1,5 @@
+drop index levels_pair_low_idx;
+drop index levels_pair_high_idx;
+drop index swings_pair_price_idx;
+drop index order_blocks_pair_low_high_idx;
+drop index fvgs_pair_low_high_idx;
@@ -0,0 +1,5 @@
+create index fvgs_pair_low_high_idx on fvgs (pair, low, high);
+create index order_blocks_pair_low_high_idx on order_blocks (pair, low, high);
+create index swings_pair_price_idx on swings (pair, price);
+create index levels_pair_high_idx on levels (pair, high);
+create index levels_pair_low_idx on levels (pair, low);
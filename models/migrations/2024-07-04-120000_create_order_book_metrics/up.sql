@@ -0,0 +1,28 @@
+create table order_book_metrics (
+    pair text not null,
+    time timestamptz not null,
+    best_bid decimal not null,
+    best_ask decimal not null,
+    spread decimal not null,
+    bid_size decimal not null,
+    ask_size decimal not null,
+    bid_depth decimal not null,
+    ask_depth decimal not null,
+    imbalance decimal not null,
+    rolling_imbalance decimal not null,
+    bid_wall_price decimal default null,
+    bid_wall_size decimal default null,
+    ask_wall_price decimal default null,
+    ask_wall_size decimal default null,
+    primary key (pair, time)
+);
+
+select create_hypertable('order_book_metrics', by_range('time', interval '1 day'));
+
+alter table order_book_metrics set (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'pair',
+    timescaledb.compress_orderby = 'time desc'
+);
+
+select add_compression_policy('order_book_metrics', interval '7 days');
@@ -0,0 +1 @@
+alter table candles add column last_trade_id bigint;
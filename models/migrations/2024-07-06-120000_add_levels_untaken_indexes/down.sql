@@ -0,0 +1,3 @@
+drop index levels_pair_incomplete_idx;
+drop index levels_pair_untaken_low_idx;
+drop index levels_pair_untaken_high_idx;
@@ -0,0 +1,3 @@
+create index levels_pair_untaken_high_idx on levels (pair, high) where high_taken_time is null;
+create index levels_pair_untaken_low_idx on levels (pair, low) where low_taken_time is null;
+create index levels_pair_incomplete_idx on levels (pair) where not complete;
@@ -16,4 +16,164 @@ pub struct Candle {
     low: Decimal,
     close: Decimal,
     size_in_millis: i64,
+    /// Flat candle created for a period without any tick.
+    #[builder(default)]
+    #[serde(default)]
+    synthetic: bool,
+    /// Traded volume in base currency.
+    #[builder(default)]
+    #[serde(default)]
+    volume: Decimal,
+    /// Traded volume in quote currency.
+    #[builder(default)]
+    #[serde(default)]
+    quote_volume: Decimal,
+    #[builder(default)]
+    #[serde(default)]
+    trade_count: i64,
+    #[builder(default)]
+    #[serde(default)]
+    vwap: Option<Decimal>,
+    /// Built from exchange candles fetched after the fact rather than from live ticks.
+    #[builder(default)]
+    #[serde(default)]
+    backfilled: bool,
+    /// Exchange timestamp of the latest tick applied to the candle.
+    #[builder(default)]
+    #[serde(default)]
+    last_tick_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Ticker sequence of the latest ticker tick applied to the candle.
+    #[builder(default)]
+    #[serde(default)]
+    last_sequence: Option<i64>,
+    /// `time` for regular candles, or the tag of the bar type the candle was built as.
+    #[builder(default = "default_bar_type()")]
+    #[serde(default = "default_bar_type")]
+    bar_type: String,
+    /// Trade id of the latest trade applied to the candle.
+    #[builder(default)]
+    #[serde(default)]
+    last_trade_id: Option<i64>,
+}
+
+/// Exchange sequence of a tick. Each source numbers its ticks on its own, so
+/// sequences are only compared within a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TickSequence {
+    /// `sequence_num` of a ticker message.
+    Ticker(i64),
+    /// `trade_id` of a market trade.
+    Trade(i64),
+}
+
+fn default_bar_type() -> String {
+    return "time".to_owned();
+}
+
+impl Candle {
+    pub fn close_time(&self) -> chrono::DateTime<chrono::Utc> {
+        return self.open_time + chrono::Duration::milliseconds(self.size_in_millis);
+    }
+
+    /// Stretches the candle so it ends at `time` at the earliest, for bars that
+    /// are not aggregated on a fixed period.
+    pub fn extend_to(&mut self, time: chrono::DateTime<chrono::Utc>) {
+        self.size_in_millis = self
+            .size_in_millis
+            .max((time - self.open_time).num_milliseconds());
+    }
+
+    pub fn update_price(&mut self, price: Decimal) {
+        if self.synthetic {
+            self.open = price;
+            self.high = price;
+            self.low = price;
+            self.synthetic = false;
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+    }
+
+    /// Applies a tick observed at `time`, ordered by `(time, sequence)` against
+    /// the latest applied tick of the same source.
+    ///
+    /// A tick older than the latest one only extends high and low, so a delayed
+    /// message never sets a stale close. Returns `false` for a duplicate of the
+    /// latest tick, which is ignored. Older trades delivered again are not
+    /// detected here, see `CandleStore::accept`.
+    pub fn apply_tick(
+        &mut self,
+        price: Decimal,
+        size: Option<Decimal>,
+        time: chrono::DateTime<chrono::Utc>,
+        sequence: Option<TickSequence>,
+    ) -> bool {
+        let (value, last_value) = match sequence {
+            Some(TickSequence::Trade(x)) => (Some(x), self.last_trade_id),
+            Some(TickSequence::Ticker(x)) => (Some(x), self.last_sequence),
+            None => (None, self.last_sequence),
+        };
+        let key = (Some(time), value);
+        let latest = (self.last_tick_time, last_value);
+        if key == latest {
+            return false;
+        }
+        if key < latest && !self.synthetic {
+            self.high = self.high.max(price);
+            self.low = self.low.min(price);
+        } else {
+            self.update_price(price);
+            self.last_tick_time = Some(time);
+            match sequence {
+                Some(TickSequence::Trade(x)) => self.last_trade_id = Some(x),
+                _ => self.last_sequence = value,
+            }
+        }
+        if let Some(size) = size {
+            self.add_volume(size, price * size, 1);
+        }
+        return true;
+    }
+
+    pub fn add_volume(&mut self, volume: Decimal, quote_volume: Decimal, trade_count: i64) {
+        self.volume += volume;
+        self.quote_volume += quote_volume;
+        self.trade_count += trade_count;
+        self.vwap = if self.volume.is_zero() {
+            None
+        } else {
+            Some(self.quote_volume / self.volume)
+        };
+    }
+
+    /// Aggregates a candle of a lower timeframe that belongs to this candle's period.
+    pub fn merge(&mut self, other: &Candle) {
+        self.backfilled |= other.backfilled;
+        if *other.synthetic() {
+            return;
+        }
+        if self.synthetic {
+            self.open = other.open;
+            self.high = other.high;
+            self.low = other.low;
+            self.synthetic = false;
+        }
+        self.high = self.high.max(other.high);
+        self.low = self.low.min(other.low);
+        // A candle backfilled after later ticks were applied keeps their close.
+        if self.last_tick_time.is_none_or(|x| x < other.close_time()) {
+            self.close = other.close;
+        }
+        self.add_volume(other.volume, other.quote_volume, other.trade_count);
+        if other.last_tick_time > self.last_tick_time {
+            self.last_tick_time = other.last_tick_time;
+            self.last_sequence = other.last_sequence;
+        } else if other.last_tick_time == self.last_tick_time {
+            self.last_sequence = self.last_sequence.max(other.last_sequence);
+        }
+        // Trade ids increase over time, unlike ticker sequences restarting with
+        // every connection.
+        self.last_trade_id = self.last_trade_id.max(other.last_trade_id);
+    }
 }
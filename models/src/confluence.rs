@@ -0,0 +1,171 @@
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    fvg::FVG,
+    level::Level,
+    order_block::OrderBlock,
+    schema::{fvgs, levels, order_blocks, swings},
+    swing::Swing,
+};
+
+/// Longest candle of any timeframe, a month counting as 31 days.
+const MAX_CANDLE_DAYS: i64 = 31;
+
+/// Open structures of every timeframe of a pair containing, or within
+/// `distance` of, a price at a point in time.
+#[derive(Debug, Default, Serialize)]
+pub struct Confluence {
+    pub fvgs: Vec<FVG>,
+    pub order_blocks: Vec<OrderBlock>,
+    pub swings: Vec<Swing>,
+    /// Completed levels whose high or low is near the price and not taken.
+    pub levels: Vec<Level>,
+}
+
+impl Confluence {
+    /// Number of structures found.
+    pub fn count(&self) -> usize {
+        return self.fvgs.len() + self.order_blocks.len() + self.swings.len() + self.levels.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.count() == 0;
+    }
+}
+
+/// Time at which the candle of `timeframe` opened at `open_time` closed, and
+/// what it formed became known. Bars, which have no fixed duration, are known
+/// from their open time.
+pub fn known_time(
+    open_time: chrono::DateTime<chrono::Utc>,
+    timeframe: &str,
+) -> chrono::DateTime<chrono::Utc> {
+    return match timeframe.parse::<types::Timeframe>() {
+        Ok(timeframe) => open_time + timeframe.nominal_duration(),
+        Err(_) => open_time,
+    };
+}
+
+/// FVGs, order blocks, swings and levels of `pair` open at `time` whose range
+/// is within `distance` of `price`.
+///
+/// Only what was known at `time` is returned: a structure is included once
+/// the candle forming it closed, and until the candle closing it closed.
+pub fn find_confluence(
+    pg_conn: &mut PgConnection,
+    pair: &str,
+    time: chrono::DateTime<chrono::Utc>,
+    price: Decimal,
+    distance: Decimal,
+) -> QueryResult<Confluence> {
+    let (above, below) = (price + distance, price - distance);
+    // Structures closed by a candle opening this early are closed at `time` on
+    // any timeframe.
+    let closed_before = time - chrono::Duration::days(MAX_CANDLE_DAYS);
+    let is_open = |open_time, timeframe: &str, close_time: Option<_>| {
+        return known_time(open_time, timeframe) <= time
+            && close_time.is_none_or(|x| known_time(x, timeframe) > time);
+    };
+
+    let fvgs: Vec<FVG> = fvgs::table
+        .filter(
+            fvgs::pair
+                .eq(pair)
+                .and(fvgs::low.le(above))
+                .and(fvgs::high.ge(below))
+                .and(fvgs::filtered.eq(false))
+                .and(fvgs::open_time.lt(time))
+                .and(
+                    fvgs::close_time
+                        .is_null()
+                        .or(fvgs::close_time.gt(closed_before)),
+                ),
+        )
+        .select(FVG::as_select())
+        .order((fvgs::open_time.asc(), fvgs::timeframe.asc()))
+        .get_results(pg_conn)?;
+
+    let order_blocks: Vec<OrderBlock> = order_blocks::table
+        .filter(
+            order_blocks::pair
+                .eq(pair)
+                .and(order_blocks::low.le(above))
+                .and(order_blocks::high.ge(below))
+                .and(order_blocks::break_time.lt(time))
+                .and(
+                    order_blocks::mitigation_time
+                        .is_null()
+                        .or(order_blocks::mitigation_time.gt(closed_before)),
+                ),
+        )
+        .select(OrderBlock::as_select())
+        .order((order_blocks::open_time.asc(), order_blocks::timeframe.asc()))
+        .get_results(pg_conn)?;
+
+    let swings: Vec<Swing> = swings::table
+        .filter(
+            swings::pair
+                .eq(pair)
+                .and(swings::price.between(below, above))
+                .and(swings::open_time.lt(time))
+                .and(
+                    swings::confirmation_time
+                        .is_null()
+                        .or(swings::confirmation_time.le(time)),
+                )
+                .and(
+                    swings::close_time
+                        .is_null()
+                        .or(swings::close_time.gt(closed_before)),
+                ),
+        )
+        .select(Swing::as_select())
+        .order((swings::open_time.asc(), swings::timeframe.asc()))
+        .get_results(pg_conn)?;
+
+    let levels: Vec<Level> = levels::table
+        .filter(
+            levels::pair.eq(pair).and(levels::close_time.le(time)).and(
+                levels::high
+                    .between(below, above)
+                    .and(
+                        levels::high_taken_time
+                            .is_null()
+                            .or(levels::high_taken_time.ge(time)),
+                    )
+                    .or(levels::low.between(below, above).and(
+                        levels::low_taken_time
+                            .is_null()
+                            .or(levels::low_taken_time.ge(time)),
+                    )),
+            ),
+        )
+        .select(Level::as_select())
+        .order((levels::open_time.asc(), levels::name.asc()))
+        .get_results(pg_conn)?;
+
+    return Ok(Confluence {
+        fvgs: fvgs
+            .into_iter()
+            .filter(|x| is_open(*x.open_time(), x.timeframe(), *x.close_time()))
+            .collect(),
+        order_blocks: order_blocks
+            .into_iter()
+            .filter(|x| is_open(*x.break_time(), x.timeframe(), *x.mitigation_time()))
+            .collect(),
+        // The confirmation time is already the close of the confirming candle.
+        swings: swings
+            .into_iter()
+            .filter(|x| {
+                let confirmed = x.confirmation_time().is_some()
+                    || known_time(*x.open_time(), x.timeframe()) <= time;
+                return confirmed
+                    && x.close_time()
+                        .is_none_or(|close_time| known_time(close_time, x.timeframe()) > time);
+            })
+            .collect(),
+        levels,
+    });
+}
@@ -0,0 +1,95 @@
+use derive_getters::Getters;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::swing::Swing;
+
+/// Range between the latest confirmed swing high and swing low of a pair and
+/// timeframe, as of the candle opened at `open_time`.
+///
+/// Above `equilibrium` is the premium zone, below it the discount zone. The
+/// OTE (optimal trade entry) levels are the 62%, 70.5% and 79% retracements of
+/// the last leg: down from the high when the low came first (`bull`), up from
+/// the low otherwise (`bear`).
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct DealingRange {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    timeframe: String,
+    high: Decimal,
+    high_time: chrono::DateTime<chrono::Utc>,
+    low: Decimal,
+    low_time: chrono::DateTime<chrono::Utc>,
+    flow: String,
+    equilibrium: Decimal,
+    ote_62: Decimal,
+    ote_705: Decimal,
+    ote_79: Decimal,
+}
+
+impl DealingRange {
+    /// Range between `high` and `low`, None when the high is not above the low.
+    pub fn new(
+        open_time: chrono::DateTime<chrono::Utc>,
+        high: &Swing,
+        low: &Swing,
+    ) -> Option<Self> {
+        let range = high.price() - low.price();
+        if range <= Decimal::ZERO {
+            return None;
+        }
+        let flow = match low.open_time() < high.open_time() {
+            true => "bull",
+            false => "bear",
+        };
+        let retracement = |percent: Decimal| {
+            let size = range * percent / Decimal::ONE_HUNDRED;
+            return match flow {
+                "bull" => high.price() - size,
+                _ => low.price() + size,
+            };
+        };
+
+        return Some(Self {
+            pair: high.pair().to_owned(),
+            open_time,
+            timeframe: high.timeframe().to_owned(),
+            high: *high.price(),
+            high_time: *high.open_time(),
+            low: *low.price(),
+            low_time: *low.open_time(),
+            flow: flow.to_owned(),
+            equilibrium: (high.price() + low.price()) / Decimal::TWO,
+            ote_62: retracement(Decimal::from(62)),
+            ote_705: retracement(Decimal::new(705, 1)),
+            ote_79: retracement(Decimal::from(79)),
+        });
+    }
+
+    /// `premium`, `discount` or `equilibrium` depending on where `price` is in
+    /// the range. Prices outside the range are in the zone of their side.
+    pub fn zone(&self, price: Decimal) -> &'static str {
+        return match price.cmp(&self.equilibrium) {
+            std::cmp::Ordering::Greater => "premium",
+            std::cmp::Ordering::Less => "discount",
+            std::cmp::Ordering::Equal => "equilibrium",
+        };
+    }
+
+    /// Whether `price` is between the 62% and 79% retracements.
+    pub fn is_in_ote(&self, price: Decimal) -> bool {
+        return price >= self.ote_62.min(self.ote_79) && price <= self.ote_62.max(self.ote_79);
+    }
+
+    /// Values stored in `indicator_values`, by output.
+    pub fn outputs(&self) -> Vec<(&'static str, Decimal)> {
+        return vec![
+            ("high", self.high),
+            ("low", self.low),
+            ("equilibrium", self.equilibrium),
+            ("ote_62", self.ote_62),
+            ("ote_705", self.ote_705),
+            ("ote_79", self.ote_79),
+        ];
+    }
+}
@@ -4,6 +4,8 @@ use diesel::prelude::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
+use crate::Candle;
+
 #[derive(Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize)]
 #[diesel(table_name = crate::schema::fvgs)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -15,4 +17,97 @@ pub struct FVG {
     low: Decimal,
     flow: String,
     close_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Open time of the first candle trading into the gap.
+    #[builder(default)]
+    #[serde(default)]
+    touch_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Deepest part of the gap traded into, from 0 to 100.
+    #[builder(default)]
+    #[serde(default)]
+    fill_percent: Decimal,
+    /// Open time of the first candle reaching the middle of the gap, its
+    /// consequent encroachment.
+    #[builder(default)]
+    #[serde(default)]
+    ce_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Open time of the first candle rejected by the gap from the other side
+    /// after it was closed through, when it became an inverse FVG.
+    #[builder(default)]
+    #[serde(default)]
+    inversion_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Gap that did not pass the filters of the indicator, only recorded for research.
+    #[builder(default)]
+    #[serde(default)]
+    filtered: bool,
+}
+
+/// What a candle changed in the mitigation of an FVG.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Mitigation {
+    pub touched: bool,
+    pub filled: bool,
+    /// The fill reached the middle of the gap, its consequent encroachment.
+    pub encroached: bool,
+    pub inverted: bool,
+}
+
+impl Mitigation {
+    pub fn is_empty(&self) -> bool {
+        return *self == Mitigation::default();
+    }
+}
+
+impl FVG {
+    /// Applies a candle that closed after the gap was formed.
+    ///
+    /// While the gap is open, the candle can touch it and fill it deeper. Once
+    /// closed through, a later candle trading back into the gap and closing
+    /// out of it on the side it was closed through inverts it.
+    pub fn mitigate(&mut self, candle: &Candle) -> Mitigation {
+        let mut mitigation = Mitigation::default();
+        let bull = self.flow == "bull";
+
+        match self.close_time {
+            None => {
+                let depth = match bull {
+                    true => self.high - candle.low(),
+                    false => candle.high() - self.low,
+                };
+                if depth < Decimal::ZERO {
+                    return mitigation;
+                }
+                if self.touch_time.is_none() {
+                    self.touch_time = Some(*candle.open_time());
+                    mitigation.touched = true;
+                }
+                let size = self.high - self.low;
+                let fill_percent = match size.is_zero() {
+                    true => Decimal::ONE_HUNDRED,
+                    false => (depth / size * Decimal::ONE_HUNDRED).min(Decimal::ONE_HUNDRED),
+                };
+                if fill_percent > self.fill_percent {
+                    self.fill_percent = fill_percent;
+                    mitigation.filled = true;
+                }
+                if self.ce_time.is_none() && self.fill_percent >= Decimal::from(50) {
+                    self.ce_time = Some(*candle.open_time());
+                    mitigation.encroached = true;
+                }
+            }
+            Some(close_time) => {
+                if self.inversion_time.is_some() || close_time >= *candle.open_time() {
+                    return mitigation;
+                }
+                let rejected = match bull {
+                    true => *candle.high() >= self.low && *candle.close() < self.low,
+                    false => *candle.low() <= self.high && *candle.close() > self.high,
+                };
+                if rejected {
+                    self.inversion_time = Some(*candle.open_time());
+                    mitigation.inverted = true;
+                }
+            }
+        }
+        return mitigation;
+    }
 }
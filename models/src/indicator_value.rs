@@ -0,0 +1,18 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// One output of an indicator, e.g. the `signal` line of `macd-12-26-9`, for a candle.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::indicator_values)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IndicatorValue {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    timeframe: String,
+    indicator: String,
+    output: String,
+    value: Decimal,
+}
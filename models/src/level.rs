@@ -0,0 +1,81 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::Candle;
+
+/// High and low of a period (`day`, `week`, `month`) or of a trading session
+/// (e.g. `london`) of a pair.
+#[derive(
+    Debug,
+    Clone,
+    Queryable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    Builder,
+    Getters,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(table_name = crate::schema::levels)]
+#[diesel(primary_key(pair, open_time, name))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Level {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    name: String,
+    close_time: chrono::DateTime<chrono::Utc>,
+    high: Decimal,
+    low: Decimal,
+    /// The period or session is over, its high and low are final.
+    #[builder(default)]
+    complete: bool,
+    /// Open time of the first candle trading above the completed high.
+    #[builder(default)]
+    high_taken_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Open time of the first candle trading below the completed low.
+    #[builder(default)]
+    low_taken_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Level {
+    /// Extends the level with a candle opened during its period, completing it
+    /// when the candle closes at the end of the period.
+    pub fn extend(&mut self, candle: &Candle) {
+        self.high = self.high.max(*candle.high());
+        self.low = self.low.min(*candle.low());
+        self.complete |= candle.close_time() >= self.close_time;
+    }
+
+    /// Completes the level when `candle` opened after its period ended without
+    /// a candle closing it, returning whether it did.
+    pub fn complete_by(&mut self, candle: &Candle) -> bool {
+        if self.complete || *candle.open_time() < self.close_time {
+            return false;
+        }
+        self.complete = true;
+        return true;
+    }
+
+    /// Marks the high and low of a completed level traded through by `candle`,
+    /// returning the sides taken (`high`, `low`).
+    pub fn take(&mut self, candle: &Candle) -> Vec<&'static str> {
+        let mut taken = Vec::new();
+        if !self.complete || *candle.open_time() < self.close_time {
+            return taken;
+        }
+        if self.high_taken_time.is_none() && *candle.high() > self.high {
+            self.high_taken_time = Some(*candle.open_time());
+            taken.push("high");
+        }
+        if self.low_taken_time.is_none() && *candle.low() < self.low {
+            self.low_taken_time = Some(*candle.open_time());
+            taken.push("low");
+        }
+        return taken;
+    }
+}
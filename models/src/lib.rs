@@ -1,7 +1,15 @@
 pub mod candle;
+pub mod confluence;
+pub mod dealing_range;
 pub mod fvg;
+pub mod indicator_value;
+pub mod level;
+pub mod market_structure;
+pub mod order_block;
+pub mod order_book_metric;
 pub mod trade;
 pub mod swing;
+pub mod volume_profile;
 pub mod schema;
 
 pub use candle::Candle;
@@ -0,0 +1,157 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{swing::Swing, Candle};
+
+/// Trend of a pair on a timeframe, with the swing high and low that a close
+/// has to break to continue or reverse it.
+#[derive(
+    Debug,
+    Clone,
+    Queryable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    Builder,
+    Getters,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(table_name = crate::schema::market_structures)]
+#[diesel(primary_key(pair, timeframe))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct MarketStructure {
+    pair: String,
+    timeframe: String,
+    /// `bull` or `bear`, unknown until the first break of structure.
+    #[builder(default)]
+    trend: Option<String>,
+    #[builder(default)]
+    swing_high: Option<Decimal>,
+    #[builder(default)]
+    swing_high_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// `hh` (higher high) or `lh` (lower high) against the previous swing high.
+    #[builder(default)]
+    swing_high_label: Option<String>,
+    #[builder(default)]
+    swing_high_broken: bool,
+    #[builder(default)]
+    swing_low: Option<Decimal>,
+    #[builder(default)]
+    swing_low_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// `hl` (higher low) or `ll` (lower low) against the previous swing low.
+    #[builder(default)]
+    swing_low_label: Option<String>,
+    #[builder(default)]
+    swing_low_broken: bool,
+    /// Open time of the last candle applied.
+    updated_time: chrono::DateTime<chrono::Utc>,
+}
+
+/// A close through the swing high (`bull`) or swing low (`bear`) of the market
+/// structure: `bos` when it goes with the trend, `choch` when it reverses it.
+#[derive(
+    Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize,
+)]
+#[diesel(table_name = crate::schema::structure_breaks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct StructureBreak {
+    pair: String,
+    /// Open time of the breaking candle.
+    open_time: chrono::DateTime<chrono::Utc>,
+    timeframe: String,
+    kind: String,
+    flow: String,
+    /// Open time of the broken swing.
+    swing_time: chrono::DateTime<chrono::Utc>,
+    swing_price: Decimal,
+    /// Close of the breaking candle.
+    close: Decimal,
+}
+
+impl MarketStructure {
+    /// Open time of the latest swing applied.
+    pub fn last_swing_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        return self.swing_high_time.max(self.swing_low_time);
+    }
+
+    /// Makes `swing` the swing high (`bear` flow) or swing low (`bull` flow) to break.
+    pub fn add_swing(&mut self, swing: &Swing) {
+        let price = *swing.price();
+        match swing.flow().as_str() {
+            "bear" => {
+                self.swing_high_label = self
+                    .swing_high
+                    .map(|x| if price > x { "hh" } else { "lh" }.to_owned());
+                self.swing_high = Some(price);
+                self.swing_high_time = Some(*swing.open_time());
+                self.swing_high_broken = false;
+            }
+            _ => {
+                self.swing_low_label = self
+                    .swing_low
+                    .map(|x| if price > x { "hl" } else { "ll" }.to_owned());
+                self.swing_low = Some(price);
+                self.swing_low_time = Some(*swing.open_time());
+                self.swing_low_broken = false;
+            }
+        }
+    }
+
+    /// Applies a closed candle, returning the structure it broke.
+    ///
+    /// Each swing is broken once, by the first close through it.
+    pub fn apply_candle(&mut self, candle: &Candle) -> Vec<StructureBreak> {
+        self.updated_time = *candle.open_time();
+        let mut breaks = Vec::new();
+
+        if let (Some(price), Some(time), false) = (
+            self.swing_high,
+            self.swing_high_time,
+            self.swing_high_broken,
+        ) {
+            if *candle.close() > price {
+                self.swing_high_broken = true;
+                breaks.push(self.structure_break(candle, "bull", time, price));
+            }
+        }
+        if let (Some(price), Some(time), false) =
+            (self.swing_low, self.swing_low_time, self.swing_low_broken)
+        {
+            if *candle.close() < price {
+                self.swing_low_broken = true;
+                breaks.push(self.structure_break(candle, "bear", time, price));
+            }
+        }
+        return breaks;
+    }
+
+    fn structure_break(
+        &mut self,
+        candle: &Candle,
+        flow: &str,
+        swing_time: chrono::DateTime<chrono::Utc>,
+        swing_price: Decimal,
+    ) -> StructureBreak {
+        let kind = match self.trend.as_deref() {
+            Some(trend) if trend != flow => "choch",
+            _ => "bos",
+        };
+        self.trend = Some(flow.to_owned());
+
+        return StructureBreak {
+            pair: self.pair.to_owned(),
+            open_time: *candle.open_time(),
+            timeframe: self.timeframe.to_owned(),
+            kind: kind.to_owned(),
+            flow: flow.to_owned(),
+            swing_time,
+            swing_price,
+            close: *candle.close(),
+        };
+    }
+}
@@ -0,0 +1,24 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Last opposite candle before a displacement that broke structure: a bearish
+/// candle for a `bull` order block, a bullish one for a `bear` order block.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::order_blocks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OrderBlock {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    timeframe: String,
+    high: Decimal,
+    low: Decimal,
+    flow: String,
+    /// Open time of the candle that broke structure.
+    break_time: chrono::DateTime<chrono::Utc>,
+    /// Open time of the first candle trading back into the order block.
+    #[builder(default)]
+    mitigation_time: Option<chrono::DateTime<chrono::Utc>>,
+}
@@ -0,0 +1,47 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// State of the order book of a pair, sampled at a fixed interval from the
+/// level2 channel.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::order_book_metrics)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OrderBookMetric {
+    pair: String,
+    time: chrono::DateTime<chrono::Utc>,
+    best_bid: Decimal,
+    best_ask: Decimal,
+    spread: Decimal,
+    /// Quantity resting at the best bid.
+    bid_size: Decimal,
+    /// Quantity resting at the best ask.
+    ask_size: Decimal,
+    /// Quantity of the bids within the depth band below the mid price.
+    bid_depth: Decimal,
+    /// Quantity of the asks within the depth band above the mid price.
+    ask_depth: Decimal,
+    /// (bid_depth - ask_depth) / (bid_depth + ask_depth), from -1 to 1.
+    imbalance: Decimal,
+    /// Mean imbalance of the last samples.
+    rolling_imbalance: Decimal,
+    /// Largest bid of the depth band when it is a wall.
+    #[builder(default)]
+    bid_wall_price: Option<Decimal>,
+    #[builder(default)]
+    bid_wall_size: Option<Decimal>,
+    /// Largest ask of the depth band when it is a wall.
+    #[builder(default)]
+    ask_wall_price: Option<Decimal>,
+    #[builder(default)]
+    ask_wall_size: Option<Decimal>,
+}
+
+impl OrderBookMetric {
+    /// Mid price between the best bid and ask.
+    pub fn mid(&self) -> Decimal {
+        return (self.best_bid + self.best_ask) / Decimal::TWO;
+    }
+}
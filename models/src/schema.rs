@@ -10,6 +10,16 @@ diesel::table! {
         low -> Numeric,
         close -> Numeric,
         size_in_millis -> Int8,
+        synthetic -> Bool,
+        volume -> Numeric,
+        quote_volume -> Numeric,
+        trade_count -> Int8,
+        vwap -> Nullable<Numeric>,
+        backfilled -> Bool,
+        last_tick_time -> Nullable<Timestamptz>,
+        last_sequence -> Nullable<Int8>,
+        bar_type -> Text,
+        last_trade_id -> Nullable<Int8>,
     }
 }
 
@@ -22,6 +32,99 @@ diesel::table! {
         low -> Numeric,
         flow -> Text,
         close_time -> Nullable<Timestamptz>,
+        touch_time -> Nullable<Timestamptz>,
+        fill_percent -> Numeric,
+        ce_time -> Nullable<Timestamptz>,
+        inversion_time -> Nullable<Timestamptz>,
+        filtered -> Bool,
+    }
+}
+
+diesel::table! {
+    indicator_values (pair, open_time, timeframe, indicator, output) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        timeframe -> Text,
+        indicator -> Text,
+        output -> Text,
+        value -> Numeric,
+    }
+}
+
+diesel::table! {
+    levels (pair, open_time, name) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        name -> Text,
+        close_time -> Timestamptz,
+        high -> Numeric,
+        low -> Numeric,
+        complete -> Bool,
+        high_taken_time -> Nullable<Timestamptz>,
+        low_taken_time -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    market_structures (pair, timeframe) {
+        pair -> Text,
+        timeframe -> Text,
+        trend -> Nullable<Text>,
+        swing_high -> Nullable<Numeric>,
+        swing_high_time -> Nullable<Timestamptz>,
+        swing_high_label -> Nullable<Text>,
+        swing_high_broken -> Bool,
+        swing_low -> Nullable<Numeric>,
+        swing_low_time -> Nullable<Timestamptz>,
+        swing_low_label -> Nullable<Text>,
+        swing_low_broken -> Bool,
+        updated_time -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    order_book_metrics (pair, time) {
+        pair -> Text,
+        time -> Timestamptz,
+        best_bid -> Numeric,
+        best_ask -> Numeric,
+        spread -> Numeric,
+        bid_size -> Numeric,
+        ask_size -> Numeric,
+        bid_depth -> Numeric,
+        ask_depth -> Numeric,
+        imbalance -> Numeric,
+        rolling_imbalance -> Numeric,
+        bid_wall_price -> Nullable<Numeric>,
+        bid_wall_size -> Nullable<Numeric>,
+        ask_wall_price -> Nullable<Numeric>,
+        ask_wall_size -> Nullable<Numeric>,
+    }
+}
+
+diesel::table! {
+    order_blocks (pair, open_time, timeframe) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        timeframe -> Text,
+        high -> Numeric,
+        low -> Numeric,
+        flow -> Text,
+        break_time -> Timestamptz,
+        mitigation_time -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    structure_breaks (pair, open_time, timeframe, flow) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        timeframe -> Text,
+        kind -> Text,
+        flow -> Text,
+        swing_time -> Timestamptz,
+        swing_price -> Numeric,
+        close -> Numeric,
     }
 }
 
@@ -33,6 +136,7 @@ diesel::table! {
         price -> Numeric,
         flow -> Text,
         close_time -> Nullable<Timestamptz>,
+        confirmation_time -> Nullable<Timestamptz>,
     }
 }
 
@@ -52,9 +156,44 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    volume_profile_bins (pair, open_time, name, price) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        name -> Text,
+        price -> Numeric,
+        volume -> Numeric,
+        low_volume_node -> Bool,
+    }
+}
+
+diesel::table! {
+    volume_profiles (pair, open_time, name) {
+        pair -> Text,
+        open_time -> Timestamptz,
+        name -> Text,
+        close_time -> Timestamptz,
+        bin_size -> Numeric,
+        volume -> Numeric,
+        poc -> Nullable<Numeric>,
+        value_area_high -> Nullable<Numeric>,
+        value_area_low -> Nullable<Numeric>,
+        complete -> Bool,
+        updated_time -> Timestamptz,
+    }
+}
+
 diesel::allow_tables_to_appear_in_same_query!(
     candles,
     fvgs,
+    indicator_values,
+    levels,
+    market_structures,
+    order_blocks,
+    order_book_metrics,
+    structure_breaks,
     swings,
     trades,
+    volume_profile_bins,
+    volume_profiles,
 );
@@ -14,4 +14,9 @@ pub struct Swing {
     price: Decimal,
     flow: String,
     close_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Close of the last candle needed to confirm the swing, when it became
+    /// knowable. `open_time` is the time of the pivot candle.
+    #[builder(default)]
+    #[serde(default)]
+    confirmation_time: Option<chrono::DateTime<chrono::Utc>>,
 }
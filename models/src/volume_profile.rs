@@ -0,0 +1,163 @@
+use derive_builder::Builder;
+use derive_getters::Getters;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::Candle;
+
+/// Traded volume by price of a period (`day`, `week`), a trading session
+/// (e.g. `london`) or a fixed range of a pair, binned by `bin_size`.
+#[derive(
+    Debug,
+    Clone,
+    Queryable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    Builder,
+    Getters,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(table_name = crate::schema::volume_profiles)]
+#[diesel(primary_key(pair, open_time, name))]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VolumeProfile {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    name: String,
+    close_time: chrono::DateTime<chrono::Utc>,
+    bin_size: Decimal,
+    #[builder(default)]
+    volume: Decimal,
+    /// Point of control, the middle of the bin with the most volume.
+    #[builder(default)]
+    poc: Option<Decimal>,
+    #[builder(default)]
+    value_area_high: Option<Decimal>,
+    #[builder(default)]
+    value_area_low: Option<Decimal>,
+    /// The window is over, the profile is final.
+    #[builder(default)]
+    complete: bool,
+    /// Open time of the last candle added to the profile.
+    updated_time: chrono::DateTime<chrono::Utc>,
+}
+
+/// Volume traded in `[price, price + bin_size)` during a volume profile's
+/// window. Prices without volume have no bin.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Builder, Getters, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::volume_profile_bins)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VolumeProfileBin {
+    pair: String,
+    open_time: chrono::DateTime<chrono::Utc>,
+    name: String,
+    price: Decimal,
+    volume: Decimal,
+    /// Local minimum of volume, below the low volume threshold of the profile.
+    #[builder(default)]
+    low_volume_node: bool,
+}
+
+impl VolumeProfile {
+    /// Volume of `candle` spread evenly over its range, by bin price.
+    pub fn distribute(&self, candle: &Candle) -> Vec<(Decimal, Decimal)> {
+        let (high, low, volume) = (*candle.high(), *candle.low(), *candle.volume());
+        if volume <= Decimal::ZERO || self.bin_size <= Decimal::ZERO {
+            return Vec::new();
+        }
+        let first = (low / self.bin_size).floor() * self.bin_size;
+        if high <= low {
+            return vec![(first, volume)];
+        }
+
+        let mut bins = Vec::new();
+        let mut price = first;
+        while price < high {
+            let overlap = high.min(price + self.bin_size) - low.max(price);
+            if overlap > Decimal::ZERO {
+                bins.push((price, volume * overlap / (high - low)));
+            }
+            price += self.bin_size;
+        }
+        return bins;
+    }
+
+    /// Adds a candle opened during the window, completing the profile when the
+    /// candle closes at the end of the window.
+    pub fn extend(&mut self, candle: &Candle) {
+        self.volume += candle.volume();
+        self.updated_time = *candle.open_time();
+        self.complete |= candle.close_time() >= self.close_time;
+    }
+
+    /// Completes the profile when `candle` opened after its window ended
+    /// without a candle closing it, returning whether it did.
+    pub fn complete_by(&mut self, candle: &Candle) -> bool {
+        if self.complete || *candle.open_time() < self.close_time {
+            return false;
+        }
+        self.complete = true;
+        return true;
+    }
+
+    /// Sets the POC and value area from `bins`, sorted by price, and flags their
+    /// low volume nodes.
+    ///
+    /// The value area grows from the POC bin towards the neighbouring bin with
+    /// the most volume until it holds `value_area_percent` of the volume. Low
+    /// volume nodes are the bins below `low_volume_percent` of the POC volume
+    /// holding no more volume than their neighbours.
+    pub fn analyze(
+        &mut self,
+        bins: &mut [VolumeProfileBin],
+        value_area_percent: Decimal,
+        low_volume_percent: Decimal,
+    ) {
+        let poc_index = match bins.iter().enumerate().rev().max_by_key(|(_, x)| x.volume) {
+            Some((i, _)) => i,
+            None => return,
+        };
+        let total: Decimal = bins.iter().map(|x| x.volume).sum();
+        let target = total * value_area_percent / Decimal::ONE_HUNDRED;
+
+        let (mut low, mut high) = (poc_index, poc_index);
+        let mut volume = bins[poc_index].volume;
+        while volume < target {
+            let above = bins.get(high + 1).map(|x| x.volume);
+            let below = low.checked_sub(1).map(|x| bins[x].volume);
+            match (above, below) {
+                (Some(above), Some(below)) if below > above => {
+                    low -= 1;
+                    volume += below;
+                }
+                (Some(above), _) => {
+                    high += 1;
+                    volume += above;
+                }
+                (None, Some(below)) => {
+                    low -= 1;
+                    volume += below;
+                }
+                (None, None) => break,
+            }
+        }
+        let half_bin = self.bin_size / Decimal::TWO;
+        self.poc = Some(bins[poc_index].price + half_bin);
+        self.value_area_high = Some(bins[high].price + self.bin_size);
+        self.value_area_low = Some(bins[low].price);
+
+        let threshold = bins[poc_index].volume * low_volume_percent / Decimal::ONE_HUNDRED;
+        let volumes: Vec<Decimal> = bins.iter().map(|x| x.volume).collect();
+        for (i, bin) in bins.iter_mut().enumerate() {
+            bin.low_volume_node = i > 0
+                && i + 1 < volumes.len()
+                && bin.volume < threshold
+                && bin.volume <= volumes[i - 1]
+                && bin.volume <= volumes[i + 1];
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Context;
 use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection, RunQueryDsl};
-use models::{trade::{TradeBuilder, Trade}, schema::trades, Candle};
+use models::{trade::Trade, schema::trades, Candle};
 use redis::Commands;
 
 pub fn handle_candle(
@@ -36,6 +36,17 @@ pub fn handle_candle(
     if !trades.is_empty() {
         println!("filled trades: {trades:#?}");
     }
+    let channel = if is_backtest { "backtest-fill" } else { "fill" };
+    for trade in trades.iter() {
+        let _: () = redis_conn
+            .publish(
+                channel,
+                serde_json::to_string(trade).context(format!(
+                    "Stringify result for publishing on redis {channel}"
+                ))?,
+            )
+            .context(format!("Publishing to redis {channel} channel"))?;
+    }
     let trades: Vec<Trade> = diesel::update(
         trades::table.filter(
             trades::pair
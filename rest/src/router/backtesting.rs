@@ -1,4 +1,4 @@
-use std::{borrow::Cow, str::FromStr, sync::Arc};
+use std::{borrow::Cow, sync::Arc};
 
 use anyhow::Context;
 use axum::{
@@ -11,24 +11,23 @@ use axum::{
     Json, Router,
 };
 use chrono::TimeZone;
-use coinbase_advanced_api::{
-    rest::{
-        client::RestClient, products::candles::CandlesBuilder, query::Query as CoinbaseRestQuery,
-    },
-    ws::channel::{
-        ticker::{Ticker, TickerEvent},
-        EventType, Response,
-    },
+use coinbase_advanced_api::rest::{
+    client::RestClient,
+    products::candles::{CandlesBuilder, CandlesResponse},
+    query::Query as CoinbaseRestQuery,
 };
 use diesel::prelude::*;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use models::{
+    candle::CandleBuilder,
     fvg::FVG,
-    schema::{candles, fvgs, trades, swings},
+    schema::{
+        candles, fvgs, indicator_values, levels, market_structures, order_blocks, structure_breaks,
+        swings, trades, volume_profile_bins, volume_profiles,
+    },
     Candle,
 };
 use redis::Commands;
-use rust_decimal::Decimal;
 use serde::Deserialize;
 use tokio::sync::{broadcast::Receiver, Mutex};
 use types::Timeframe;
@@ -47,20 +46,6 @@ pub fn create_router() -> Router<AppState> {
     return router;
 }
 
-#[derive(Debug, Deserialize)]
-struct CandlesResponse {
-    candles: Vec<CoinbaseCandle>,
-}
-
-#[derive(Debug, Deserialize)]
-struct CoinbaseCandle {
-    start: String,
-    open: String,
-    high: String,
-    low: String,
-    close: String,
-}
-
 async fn backtest(
     State(state): State<AppState>,
     Path(product_id): Path<String>,
@@ -82,6 +67,16 @@ async fn backtest(
     diesel::delete(fvgs::table).execute(pg_conn)?;
     diesel::delete(trades::table).execute(pg_conn)?;
     diesel::delete(swings::table).execute(pg_conn)?;
+    diesel::delete(indicator_values::table).execute(pg_conn)?;
+    diesel::delete(market_structures::table).execute(pg_conn)?;
+    diesel::delete(structure_breaks::table).execute(pg_conn)?;
+    diesel::delete(order_blocks::table).execute(pg_conn)?;
+    diesel::delete(levels::table).execute(pg_conn)?;
+    diesel::delete(volume_profiles::table).execute(pg_conn)?;
+    diesel::delete(volume_profile_bins::table).execute(pg_conn)?;
+    let _: () = redis_conn
+        .publish("backtest-reset", product_id.clone())
+        .context("Publishing to redis backtest-reset channel")?;
     while start_timestamp < end_timestamp {
         let candles_request = CandlesBuilder::default()
             .product_id(Cow::Borrowed("BTC-USD"))
@@ -92,39 +87,22 @@ async fn backtest(
         let mut candles: CandlesResponse = candles_request.query(&rest_client).await?;
         candles.candles.sort_by_key(|x| x.start.clone());
         for candle in candles.candles.iter() {
-            for price in [&candle.open, &candle.low, &candle.high, &candle.close].iter() {
-                let wef = Response::<TickerEvent> {
-                    channel: "ticker".to_owned(),
-                    client_id: "".to_owned(),
-                    timestamp: chrono::Utc
-                        .timestamp_opt(i64::from_str(&candle.start)?, 0)
-                        .unwrap(),
-                    sequence_num: 0,
-                    events: vec![TickerEvent {
-                        r#type: EventType::Snapshot,
-                        tickers: vec![Ticker {
-                            r#type: "ticker".to_owned(),
-                            product_id: product_id.clone(),
-                            price: Decimal::from_str(price)?,
-                            volume_24_h: Decimal::from(0),
-                            low_24_h: Decimal::from(0),
-                            high_24_h: Decimal::from(0),
-                            low_52_w: Decimal::from(0),
-                            high_52_w: Decimal::from(0),
-                            price_percent_chg_24_h: Decimal::from(0),
-                            best_bid: None,
-                            best_bid_quantity: None,
-                            best_ask: None,
-                            best_ask_quantity: None,
-                        }],
-                    }],
-                };
-                let json_message = serde_json::to_string(&wef)?;
-                let _: () = redis_conn
-                    .publish("backtest-ticker".to_owned(), json_message)
-                    .context("Publishing to redis backtes-ticker channel")?;
-                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
-            }
+            let mut exchange_candle = CandleBuilder::default()
+                .pair(product_id.clone())
+                .open_time(candle.start_time()?)
+                .timeframe(Timeframe::Minute(1).to_string())
+                .open(candle.open)
+                .high(candle.high)
+                .low(candle.low)
+                .close(candle.close)
+                .size_in_millis(chrono::Duration::minutes(1).num_milliseconds())
+                .build()?;
+            exchange_candle.add_volume(candle.volume, candle.quote_volume(), 0);
+            let json_message = serde_json::to_string(&exchange_candle)?;
+            let _: () = redis_conn
+                .publish("backtest-exchange_candle".to_owned(), json_message)
+                .context("Publishing to redis backtest-exchange_candle channel")?;
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
         }
         start_timestamp += chrono::Duration::minutes(300);
     }
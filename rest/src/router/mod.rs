@@ -4,7 +4,7 @@ mod product;
 use axum::Router;
 use serde::Deserialize;
 use tower_http::cors::{Any, CorsLayer};
-use types::Timeframe;
+use types::CandleTimeframe;
 
 use crate::AppState;
 
@@ -23,5 +23,6 @@ pub fn create_router(state: AppState) -> Router {
 struct Pagination {
     start_timestamp: u32,
     end_timestamp: u32,
-    timeframe: Timeframe,
+    /// Timeframe, e.g. `1h`, or bar type, e.g. `ha-1h`.
+    timeframe: CandleTimeframe,
 }
@@ -5,18 +5,80 @@ use axum::{
 };
 use chrono::TimeZone;
 use diesel::prelude::*;
-use models::{schema::candles, Candle};
+use models::{
+    confluence::find_confluence,
+    indicator_value::IndicatorValue,
+    level::Level,
+    market_structure::{MarketStructure, StructureBreak},
+    schema::{
+        candles, indicator_values, levels, market_structures, structure_breaks,
+        volume_profile_bins, volume_profiles,
+    },
+    volume_profile::{VolumeProfile, VolumeProfileBin},
+    Candle,
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use types::{CandleTimeframe, Timeframe};
 
 use crate::{error::AppError, AppState};
 
 use super::Pagination;
 
 pub fn create_router() -> Router<AppState> {
-    let router = Router::new().route("/:product_id/candles", get(get_candles));
+    let router = Router::new()
+        .route("/:product_id/candles", get(get_candles))
+        .route("/:product_id/confluence", get(get_confluence))
+        .route("/:product_id/indicators", get(get_indicators))
+        .route("/:product_id/levels", get(get_levels))
+        .route("/:product_id/market_structure", get(get_market_structure))
+        .route("/:product_id/structure_breaks", get(get_structure_breaks))
+        .route("/:product_id/volume_profiles", get(get_volume_profiles));
 
     return router;
 }
 
+#[derive(Debug, Deserialize)]
+struct ConfluenceParams {
+    timestamp: u32,
+    price: Decimal,
+    /// Distance from `price` within which structures are near it, 0 when missing.
+    #[serde(default)]
+    distance: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndicatorsParams {
+    start_timestamp: u32,
+    end_timestamp: u32,
+    /// Timeframe, e.g. `1h`, or bar type, e.g. `ha-1h`.
+    timeframe: CandleTimeframe,
+    /// Indicator name with its parameters, e.g. `ema-20`. All indicators when missing.
+    indicator: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LevelsParams {
+    start_timestamp: u32,
+    end_timestamp: u32,
+    /// Period or session name, e.g. `day` or `london`. All levels when missing.
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VolumeProfilesParams {
+    start_timestamp: u32,
+    end_timestamp: u32,
+    /// Period, session or range name, e.g. `day`. All profiles when missing.
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarketStructureParams {
+    /// All timeframes when missing.
+    timeframe: Option<Timeframe>,
+}
+
 async fn get_candles(
     State(state): State<AppState>,
     Path(product_id): Path<String>,
@@ -42,3 +104,187 @@ async fn get_candles(
         .get_results(pg_conn)?;
     return Ok(Json(serde_json::json!(res)));
 }
+
+/// Open FVGs, order blocks, swings and levels of every timeframe containing or
+/// near a price at a point in time.
+async fn get_confluence(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<ConfluenceParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let time = chrono::Utc
+        .timestamp_opt(i64::from(params.timestamp), 0)
+        .unwrap();
+    let res = find_confluence(
+        pg_conn,
+        &product_id,
+        time,
+        params.price,
+        params.distance.abs(),
+    )?;
+    return Ok(Json(serde_json::json!(res)));
+}
+
+async fn get_indicators(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<IndicatorsParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let start_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.start_timestamp), 0)
+        .unwrap();
+    let end_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.end_timestamp), 0)
+        .unwrap();
+    let mut query = indicator_values::table
+        .select(IndicatorValue::as_select())
+        .filter(
+            indicator_values::pair
+                .eq(&product_id)
+                .and(indicator_values::timeframe.eq(params.timeframe.to_string()))
+                .and(indicator_values::open_time.ge(start_timestamp))
+                .and(indicator_values::open_time.le(end_timestamp)),
+        )
+        .into_boxed();
+    if let Some(indicator) = params.indicator.as_ref() {
+        query = query.filter(indicator_values::indicator.eq(indicator));
+    }
+    let res = query
+        .order((
+            indicator_values::open_time.asc(),
+            indicator_values::indicator.asc(),
+            indicator_values::output.asc(),
+        ))
+        .get_results(pg_conn)?;
+    return Ok(Json(serde_json::json!(res)));
+}
+
+/// Current trend and swings to break of every timeframe of the product.
+async fn get_market_structure(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<MarketStructureParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let mut query = market_structures::table
+        .select(MarketStructure::as_select())
+        .filter(market_structures::pair.eq(&product_id))
+        .into_boxed();
+    if let Some(timeframe) = params.timeframe.as_ref() {
+        query = query.filter(market_structures::timeframe.eq(timeframe.to_string()));
+    }
+    let res = query
+        .order(market_structures::timeframe.asc())
+        .get_results(pg_conn)?;
+    return Ok(Json(serde_json::json!(res)));
+}
+
+async fn get_structure_breaks(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<Pagination>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let start_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.start_timestamp), 0)
+        .unwrap();
+    let end_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.end_timestamp), 0)
+        .unwrap();
+    let res = structure_breaks::table
+        .select(StructureBreak::as_select())
+        .filter(
+            structure_breaks::pair
+                .eq(&product_id)
+                .and(structure_breaks::timeframe.eq(params.timeframe.to_string()))
+                .and(structure_breaks::open_time.ge(start_timestamp))
+                .and(structure_breaks::open_time.le(end_timestamp)),
+        )
+        .order(structure_breaks::open_time.asc())
+        .get_results(pg_conn)?;
+    return Ok(Json(serde_json::json!(res)));
+}
+
+/// Period and session levels starting in the requested range.
+async fn get_levels(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<LevelsParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let start_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.start_timestamp), 0)
+        .unwrap();
+    let end_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.end_timestamp), 0)
+        .unwrap();
+    let mut query = levels::table
+        .select(Level::as_select())
+        .filter(
+            levels::pair
+                .eq(&product_id)
+                .and(levels::open_time.ge(start_timestamp))
+                .and(levels::open_time.le(end_timestamp)),
+        )
+        .into_boxed();
+    if let Some(name) = params.name.as_ref() {
+        query = query.filter(levels::name.eq(name));
+    }
+    let res = query
+        .order((levels::open_time.asc(), levels::name.asc()))
+        .get_results(pg_conn)?;
+    return Ok(Json(serde_json::json!(res)));
+}
+
+/// Volume profiles starting in the requested range, with their bins.
+async fn get_volume_profiles(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Query(params): Query<VolumeProfilesParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let pg_conn = &mut state.pg_pool.get()?;
+    let start_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.start_timestamp), 0)
+        .unwrap();
+    let end_timestamp = chrono::Utc
+        .timestamp_opt(i64::from(params.end_timestamp), 0)
+        .unwrap();
+    let mut query = volume_profiles::table
+        .select(VolumeProfile::as_select())
+        .filter(
+            volume_profiles::pair
+                .eq(&product_id)
+                .and(volume_profiles::open_time.ge(start_timestamp))
+                .and(volume_profiles::open_time.le(end_timestamp)),
+        )
+        .into_boxed();
+    if let Some(name) = params.name.as_ref() {
+        query = query.filter(volume_profiles::name.eq(name));
+    }
+    let profiles = query
+        .order((
+            volume_profiles::open_time.asc(),
+            volume_profiles::name.asc(),
+        ))
+        .get_results(pg_conn)?;
+
+    let mut res = Vec::new();
+    for profile in profiles.into_iter() {
+        let bins = volume_profile_bins::table
+            .select(VolumeProfileBin::as_select())
+            .filter(
+                volume_profile_bins::pair
+                    .eq(profile.pair())
+                    .and(volume_profile_bins::open_time.eq(profile.open_time()))
+                    .and(volume_profile_bins::name.eq(profile.name())),
+            )
+            .order(volume_profile_bins::price.asc())
+            .get_results(pg_conn)?;
+        let mut value = serde_json::json!(profile);
+        value["bins"] = serde_json::json!(bins);
+        res.push(value);
+    }
+    return Ok(Json(serde_json::json!(res)));
+}
@@ -0,0 +1,51 @@
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::strategy::Strategy;
+
+/// Strategies to run and their parameters, read from the file at
+/// `STRATEGY_CONFIG`, one table per strategy named after it.
+#[derive(Debug, Deserialize)]
+pub struct Config(toml::Table);
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let path = std::env::var("STRATEGY_CONFIG").unwrap_or_else(|_| "strategy.toml".to_owned());
+        let content =
+            std::fs::read_to_string(&path).context(format!("Reading config file {path}"))?;
+
+        return toml::from_str(&content).context(format!("Parsing config file {path}"));
+    }
+
+    /// Parameters of `S`, None when it is not configured.
+    pub fn strategy<S: Strategy>(&self) -> anyhow::Result<Option<S::Config>> {
+        let params = match self.0.get(S::NAME) {
+            Some(params) => params.clone(),
+            None => return Ok(None),
+        };
+        let config = params
+            .try_into()
+            .context(format!("Parsing {} strategy config", S::NAME))?;
+        return Ok(Some(config));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::strategy::{algo_a_b::AlgoAB, combo::ComboStrategy, ifvg::IFvg};
+
+    #[test]
+    fn default_config_is_valid() {
+        let config: Config = toml::from_str(include_str!("../../strategy.toml")).unwrap();
+
+        assert!(config.strategy::<ComboStrategy>().unwrap().is_some());
+        assert!(config.strategy::<AlgoAB>().unwrap().is_none());
+        assert!(config.strategy::<IFvg>().unwrap().is_none());
+
+        let config: Config = toml::from_str("[ifvg]\nrisk_reward = 3\nnotional = 500").unwrap();
+        assert!(config.strategy::<IFvg>().unwrap().is_some());
+        let config: Config = toml::from_str("[algo_a_b]\ntimeframe = [\"1h\"]").unwrap();
+        assert!(config.strategy::<AlgoAB>().is_err());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod config;
+pub mod runner;
+pub mod strategy;
@@ -1,30 +1,16 @@
-mod fvg_close;
-mod strategy;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
-use anyhow::{bail, Context};
+use anyhow::Context;
 use diesel::{r2d2::ConnectionManager, PgConnection};
-use strategy::{combo::Combo, Strategy};
-
-async fn handle_redis_message(msg: redis::Msg, state: AppState) -> anyhow::Result<()> {
-    let channel: String = msg
-        .get_channel()
-        .context("channel from redis pubsub message")?;
-    let payload: String = msg.get_payload()?;
-
-    match channel.as_str() {
-        "fvg_close" => {
-            fvg_close::handle_fvg_close(payload, state.redis_pool, state.pg_pool, false)?
-        }
-        "backtest-fvg_close" => fvg_close::handle_fvg_close(
-            payload,
-            state.redis_pool,
-            state.pg_pool_backtest,
-            true,
-        )?,
-        _ => bail!("No handler for redis channel {channel}"),
-    };
-    return Ok(());
-}
+use strategy::{
+    config::Config,
+    runner,
+    strategy::{algo_a_b::AlgoAB, combo::ComboStrategy, ifvg::IFvg, Strategy},
+};
+use tokio::task::JoinHandle;
 
 fn init_pg_pool(is_backtest: bool) -> anyhow::Result<r2d2::Pool<ConnectionManager<PgConnection>>> {
     let database_url = match is_backtest {
@@ -59,6 +45,39 @@ pub struct AppState {
     redis_pool: r2d2::Pool<redis::Client>,
 }
 
+/// Runs `S` on live and on backtest messages when it is configured, each with
+/// its own instance.
+fn spawn_strategy<S: Strategy>(
+    state: &AppState,
+    config: &Config,
+    stop: &Arc<AtomicBool>,
+) -> anyhow::Result<Vec<JoinHandle<()>>> {
+    let strategy_config = match config.strategy::<S>()? {
+        Some(strategy_config) => strategy_config,
+        None => return Ok(Vec::new()),
+    };
+    let mut handles = Vec::new();
+    for is_backtest in [false, true] {
+        let pg_pool = match is_backtest {
+            false => state.pg_pool.clone(),
+            true => state.pg_pool_backtest.clone(),
+        };
+        let (redis_pool, strategy_config, stop) = (
+            state.redis_pool.clone(),
+            strategy_config.clone(),
+            stop.clone(),
+        );
+        // The runner blocks on the redis subscription.
+        handles.push(tokio::task::spawn_blocking(move || {
+            let res = runner::run::<S>(redis_pool, pg_pool, strategy_config, is_backtest, stop);
+            if let Err(err) = res {
+                tracing::error!("{} strategy is_backtest={is_backtest}: {err:#}", S::NAME);
+            }
+        }));
+    }
+    return Ok(handles);
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv()?;
@@ -66,18 +85,28 @@ async fn main() -> anyhow::Result<()> {
     let pg_pool = init_pg_pool(false)?;
     let pg_pool_backtest = init_pg_pool(true)?;
     let redis_pool = init_redis_pool()?;
+    let config = Config::load()?;
     let state = AppState {
         pg_pool,
         pg_pool_backtest,
         redis_pool,
     };
 
-    let state_tmp = state.clone();
-    let strategy = tokio::spawn(async move {
-        if let Err(err) = Combo::run(state_tmp).await {
-            tracing::error!("{err:#}");
-        }
-    });
-    let _ = strategy.await?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+    handles.extend(spawn_strategy::<ComboStrategy>(&state, &config, &stop)?);
+    handles.extend(spawn_strategy::<AlgoAB>(&state, &config, &stop)?);
+    handles.extend(spawn_strategy::<IFvg>(&state, &config, &stop)?);
+    if handles.is_empty() {
+        tracing::warn!("No strategy configured");
+    }
+
+    tokio::signal::ctrl_c()
+        .await
+        .context("Waiting for ctrl-c")?;
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.await?;
+    }
     return Ok(());
 }
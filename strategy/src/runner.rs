@@ -0,0 +1,114 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::{bail, Context as _};
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use serde::de::DeserializeOwned;
+use tracing::error;
+
+use crate::strategy::{context::Context, Strategy};
+
+/// Channels forwarded to the strategy hooks, `backtest-` prefixed in backtests.
+const CHANNELS: [&str; 5] = ["candle_close", "fvg", "fvg_close", "swing", "fill"];
+
+/// How often the stop flag is checked while no message is received.
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+fn parse<T: DeserializeOwned>(payload: &str, name: &str) -> anyhow::Result<T> {
+    return serde_json::from_str(payload).context(format!("Parsing redis message to {name}"));
+}
+
+/// Calls the hook of `strategy` matching the channel of a message.
+fn dispatch<S: Strategy>(
+    strategy: &mut S,
+    context: &Context,
+    channel: &str,
+    payload: &str,
+) -> anyhow::Result<()> {
+    return match channel {
+        "candle_close" => strategy.on_candle_close(context, &parse(payload, "Candle")?),
+        "fvg" | "fvg_close" => strategy.on_fvg(context, &parse(payload, "FVG")?),
+        "swing" => strategy.on_swing(context, &parse(payload, "Swing")?),
+        "fill" => strategy.on_fill(context, &parse(payload, "Trade")?),
+        _ => bail!("No handler for redis channel {channel}"),
+    };
+}
+
+/// Replaces `strategy` by a new one, even when stopping it failed.
+fn reset<S: Strategy>(
+    strategy: &mut S,
+    config: &S::Config,
+    context: &Context,
+) -> anyhow::Result<()> {
+    let stopped = strategy.on_stop(context);
+    *strategy = S::new(config.clone())?;
+    strategy.on_start(context)?;
+    return stopped;
+}
+
+/// Runs `S` on the live or backtest messages until `stop` is set or the redis
+/// connection fails, one message at a time.
+///
+/// In backtests, the strategy is stopped and created again from `config` on
+/// every `backtest-reset`.
+pub fn run<S: Strategy>(
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    config: S::Config,
+    is_backtest: bool,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let context = Context::new(redis_pool.clone(), pg_pool, is_backtest, S::NAME);
+    let mut redis_sub_conn = redis_pool
+        .get()
+        .context("Get redis_sub_conn from redis_pool")?;
+    let mut pubsub = redis_sub_conn.as_pubsub();
+    pubsub.set_read_timeout(Some(READ_TIMEOUT))?;
+    for channel in CHANNELS {
+        pubsub.subscribe(context.channel(channel))?;
+    }
+    if is_backtest {
+        pubsub.subscribe("backtest-reset")?;
+    }
+
+    let mut strategy = S::new(config.clone())?;
+    strategy
+        .on_start(&context)
+        .context(format!("Starting {} strategy", S::NAME))?;
+    let res = loop {
+        if stop.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        let msg = match pubsub.get_message() {
+            Ok(msg) => msg,
+            Err(err) if err.is_timeout() => continue,
+            Err(err) => break Err(err).context("Receiving message from redis pubsub"),
+        };
+        let channel: String = match msg.get_channel() {
+            Ok(channel) => channel,
+            Err(err) => break Err(err).context("channel from redis pubsub message"),
+        };
+        let channel = match is_backtest {
+            false => channel.as_str(),
+            true => channel.strip_prefix("backtest-").unwrap_or(&channel),
+        };
+
+        let res = match channel {
+            "reset" => reset(&mut strategy, &config, &context),
+            _ => msg
+                .get_payload::<String>()
+                .context("payload from redis pubsub message")
+                .and_then(|payload| dispatch(&mut strategy, &context, channel, &payload)),
+        };
+        if let Err(err) = res {
+            error!("{} strategy: {err:#}", S::NAME);
+        }
+    };
+
+    strategy
+        .on_stop(&context)
+        .context(format!("Stopping {} strategy", S::NAME))?;
+    return res;
+}
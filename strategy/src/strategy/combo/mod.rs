@@ -1,18 +1,39 @@
 pub mod state;
 
-use anyhow::Context;
-use async_trait::async_trait;
-use models::{fvg::FVG, swing::Swing};
-use statig::prelude::IntoStateMachineExt;
-
-use crate::AppState;
+use models::{fvg::FVG, swing::Swing, Candle};
+use serde::Deserialize;
+use statig::prelude::{IntoStateMachineExt, StateMachine};
+use types::Timeframe;
 
 use self::state::Event;
 
-use super::Strategy;
+use super::{context::Context, Strategy};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComboConfig {
+    /// Timeframe of the FVG starting a setup.
+    #[serde(default = "ComboConfig::default_htf")]
+    pub htf: Timeframe,
+    /// Timeframe of the candles testing it and of the FVG confirming it.
+    #[serde(default = "ComboConfig::default_ltf")]
+    pub ltf: Timeframe,
+}
+
+impl ComboConfig {
+    fn default_htf() -> Timeframe {
+        return Timeframe::Day(1);
+    }
+
+    fn default_ltf() -> Timeframe {
+        return Timeframe::Hour(4);
+    }
+}
 
+/// Shared storage of the combo state machine.
 pub struct Combo {
-    state: AppState,
+    htf: String,
+    ltf: String,
     v1: Option<FVG>,
     v2: Option<FVG>,
     v3: Option<Swing>,
@@ -20,56 +41,44 @@ pub struct Combo {
 }
 
 impl Combo {
-    pub fn new(state: AppState) -> Self {
+    pub fn new(config: &ComboConfig) -> Self {
         return Self {
-            state,
+            htf: config.htf.to_string(),
+            ltf: config.ltf.to_string(),
             v1: None,
             v2: None,
             v3: None,
             v4: None,
         };
     }
+}
 
+/// Feeds new FVGs and candle closes to the combo state machine.
+pub struct ComboStrategy {
+    state_machine: StateMachine<Combo>,
 }
 
-#[async_trait]
-impl Strategy for Combo {
-    async fn run(state: AppState) -> anyhow::Result<()> {
-        let mut redis_sub_conn = state.redis_pool
-            .get()
-            .context("Get redis_sub_conn from redis_pool")?;
-        let mut pg_conn = state.pg_pool
-            .get()
-            .context("Get pg_conn from pg_pool")?;
-        let mut pubsub = redis_sub_conn.as_pubsub();
-        let mut state_machine = Self::new(state).state_machine();
+impl Strategy for ComboStrategy {
+    const NAME: &'static str = "combo";
 
-        pubsub.subscribe("fvg")?;
-        pubsub.subscribe("backtest-fvg")?;
-        pubsub.subscribe("swing")?;
-        pubsub.subscribe("backtest-swing")?;
-        pubsub.subscribe("candle_close")?;
-        pubsub.subscribe("backtest-candle_close")?;
-        loop {
-            let msg = pubsub.get_message()?;
-            let channel: String = msg
-                .get_channel()
-                .context("channel from redis pubsub message")?;
-            let payload: String = msg.get_payload()?;
+    type Config = ComboConfig;
 
-            let res = match channel.as_str() {
-                "fvg" => Ok(state_machine.handle(&Event::Fvg(serde_json::from_str(&payload).context("Parsing redis message to FVG")?))),
-                "backtest-fvg" => Ok(state_machine.handle(&Event::Fvg(serde_json::from_str(&payload).context("Parsing redis message to FVG")?))),
-                "swing" => Ok(state_machine.handle(&Event::Swing(serde_json::from_str(&payload).context("Parsing redis message to Swing")?))),
-                "backtest-swing" => Ok(state_machine.handle(&Event::Swing(serde_json::from_str(&payload).context("Parsing redis message to Swing")?))),
-                "candle_close" => Ok(state_machine.handle(&Event::CandleClose(serde_json::from_str(&payload).context("Parsing redis message to Candle")?))),
-                "backtest-candle_close" => Ok(state_machine.handle(&Event::CandleClose(serde_json::from_str(&payload).context("Parsing redis message to Candle")?))),
-                _ => Err(anyhow::anyhow!("No handler for redis channel {channel}")),
-            };
+    fn new(config: Self::Config) -> anyhow::Result<Self> {
+        return Ok(Self {
+            state_machine: Combo::new(&config).state_machine(),
+        });
+    }
+
+    fn on_candle_close(&mut self, _context: &Context, candle: &Candle) -> anyhow::Result<()> {
+        self.state_machine
+            .handle(&Event::CandleClose(candle.clone()));
+        return Ok(());
+    }
 
-            if let Err(err) = res {
-                tracing::error!("{err:#}");
-            }
+    fn on_fvg(&mut self, _context: &Context, fvg: &FVG) -> anyhow::Result<()> {
+        if fvg.close_time().is_none() {
+            self.state_machine.handle(&Event::Fvg(fvg.clone()));
         }
+        return Ok(());
     }
 }
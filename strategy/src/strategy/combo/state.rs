@@ -1,10 +1,8 @@
-use models::{fvg::FVG, swing::Swing, Candle};
+use models::{fvg::FVG, Candle};
 use statig::{state_machine, Response};
-use types::Timeframe;
 
 pub enum Event {
     Fvg(FVG),
-    Swing(Swing),
     CandleClose(Candle),
 }
 
@@ -14,7 +12,7 @@ impl super::Combo {
     fn idle(&mut self, event: &Event) -> Response<State> {
         let res = match event {
             Event::Fvg(x) => {
-                if *x.timeframe() == Timeframe::Day(1).to_string() {
+                if *x.timeframe() == self.htf {
                     self.v1 = Some(x.clone());
                     println!("idle -> v1: {x:#?}");
                     Response::Transition(State::v1())
@@ -31,7 +29,7 @@ impl super::Combo {
     fn v1(&mut self, event: &Event) -> Response<State> {
         let res = match event {
             Event::CandleClose(x) => {
-                if *x.timeframe() == Timeframe::Hour(4).to_string() {
+                if *x.timeframe() == self.ltf {
                     let v1 = self.v1.as_ref().expect("v1 should be set in State::v1()");
                     if v1.flow() == "bull" && x.low() <= v1.high() {
                     println!("v1 -> v1_test: {x:#?}");
@@ -47,13 +45,12 @@ impl super::Combo {
                 }
             },
             Event::Fvg(x) => {
-                if *x.timeframe() == Timeframe::Day(1).to_string() {
+                if *x.timeframe() == self.htf {
                     self.v1 = Some(x.clone());
                     println!("new v1: {x:#?}");
                 }
                 Response::Handled
             },
-            _ => Response::Super,
         };
         return res;
     }
@@ -63,7 +60,7 @@ impl super::Combo {
         let res = match event {
             Event::Fvg(x) => {
                 let v1 = self.v1.as_ref().expect("v1 should be set in State::v1_test()");
-                if *x.timeframe() == Timeframe::Hour(4).to_string() && x.flow() == v1.flow() {
+                if *x.timeframe() == self.ltf && x.flow() == v1.flow() {
                     self.v2 = Some(x.clone());
                     println!("v1_test -> v2: {x:#?}");
                     Response::Transition(State::v2())
@@ -80,7 +77,7 @@ impl super::Combo {
     fn v2(&mut self, event: &Event) -> Response<State> {
         let res = match event {
             Event::CandleClose(x) => {
-                if *x.timeframe() == Timeframe::Hour(4).to_string() {
+                if *x.timeframe() == self.ltf {
                     self.v1.take();
                     self.v2.take();
                     self.v3.take();
@@ -0,0 +1,142 @@
+use anyhow::Context as _;
+use diesel::{prelude::*, r2d2::ConnectionManager, PgConnection};
+use models::{
+    confluence::{find_confluence, Confluence},
+    order_book_metric::OrderBookMetric,
+    schema::{indicator_values, order_book_metrics, trades},
+    trade::Trade,
+};
+use redis::Commands;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// What a strategy hook can act on: publishing signals, placing orders and
+/// querying indicators, in the database and on the channels of its mode.
+pub struct Context {
+    redis_pool: r2d2::Pool<redis::Client>,
+    pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+    is_backtest: bool,
+    strategy: &'static str,
+}
+
+impl Context {
+    pub fn new(
+        redis_pool: r2d2::Pool<redis::Client>,
+        pg_pool: r2d2::Pool<ConnectionManager<PgConnection>>,
+        is_backtest: bool,
+        strategy: &'static str,
+    ) -> Self {
+        return Self {
+            redis_pool,
+            pg_pool,
+            is_backtest,
+            strategy,
+        };
+    }
+
+    /// `channel`, prefixed with `backtest-` in backtests.
+    pub fn channel(&self, channel: &str) -> String {
+        return match self.is_backtest {
+            false => channel.to_owned(),
+            true => format!("backtest-{channel}"),
+        };
+    }
+
+    pub fn pg_conn(
+        &self,
+    ) -> anyhow::Result<r2d2::PooledConnection<ConnectionManager<PgConnection>>> {
+        return self
+            .pg_pool
+            .get()
+            .context("Getting connection from pg_pool");
+    }
+
+    pub fn publish<T: Serialize>(&self, channel: &str, value: &T) -> anyhow::Result<()> {
+        let redis_conn = &mut self
+            .redis_pool
+            .get()
+            .context("Getting connection from redis_pool")?;
+        let channel = self.channel(channel);
+        let _: () = redis_conn
+            .publish(
+                &channel,
+                serde_json::to_string(value).context(format!(
+                    "Stringify result for publishing on redis {channel}"
+                ))?,
+            )
+            .context(format!("Publishing to redis {channel} channel"))?;
+        return Ok(());
+    }
+
+    /// Publishes a signal on `strategy_<name>`.
+    pub fn emit_signal<T: Serialize>(&self, name: &str, signal: &T) -> anyhow::Result<()> {
+        return self.publish(&format!("strategy_{name}"), signal);
+    }
+
+    /// Stores `trade` for position-manager to fill and publishes it on `trade`.
+    pub fn place_order(&self, trade: Trade) -> anyhow::Result<Trade> {
+        let pg_conn = &mut self.pg_conn()?;
+        let trade: Trade = diesel::insert_into(trades::table)
+            .values(trade)
+            .get_result(pg_conn)
+            .context(format!("Inserting {} trade", self.strategy))?;
+        self.publish("trade", &trade)?;
+        return Ok(trade);
+    }
+
+    /// Output of an indicator, e.g. `rsi` of `rsi-14`, on a candle.
+    pub fn indicator_value(
+        &self,
+        pair: &str,
+        open_time: chrono::DateTime<chrono::Utc>,
+        timeframe: &str,
+        indicator: &str,
+        output: &str,
+    ) -> anyhow::Result<Option<Decimal>> {
+        let pg_conn = &mut self.pg_conn()?;
+        let value = indicator_values::table
+            .find((pair, open_time, timeframe, indicator, output))
+            .select(indicator_values::value)
+            .first(pg_conn)
+            .optional()
+            .context(format!(
+                "Getting {indicator} {output} of {pair} {timeframe}"
+            ))?;
+        return Ok(value);
+    }
+
+    /// Last order book sample of `pair` at or before `time`.
+    pub fn order_book(
+        &self,
+        pair: &str,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<Option<OrderBookMetric>> {
+        let pg_conn = &mut self.pg_conn()?;
+        let metric = order_book_metrics::table
+            .filter(
+                order_book_metrics::pair
+                    .eq(pair)
+                    .and(order_book_metrics::time.le(time)),
+            )
+            .select(OrderBookMetric::as_select())
+            .order(order_book_metrics::time.desc())
+            .first(pg_conn)
+            .optional()
+            .context(format!("Getting {pair} order book metrics"))?;
+        return Ok(metric);
+    }
+
+    /// Structures of every timeframe of `pair` open at `time` within
+    /// `distance` of `price`.
+    pub fn confluence(
+        &self,
+        pair: &str,
+        time: chrono::DateTime<chrono::Utc>,
+        price: Decimal,
+        distance: Decimal,
+    ) -> anyhow::Result<Confluence> {
+        let pg_conn = &mut self.pg_conn()?;
+        return find_confluence(pg_conn, pair, time, price, distance)
+            .context(format!("Getting {pair} confluence at {time}"));
+    }
+}
@@ -0,0 +1,78 @@
+use models::{fvg::FVG, trade::TradeBuilder};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use super::{context::Context, Strategy};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IFvgConfig {
+    /// Take profit distance, in multiples of the stop loss distance.
+    #[serde(default = "IFvgConfig::default_risk_reward")]
+    pub risk_reward: Decimal,
+    /// Quote value of every trade.
+    #[serde(default = "IFvgConfig::default_notional")]
+    pub notional: Decimal,
+}
+
+impl IFvgConfig {
+    fn default_risk_reward() -> Decimal {
+        return Decimal::TWO;
+    }
+
+    fn default_notional() -> Decimal {
+        return Decimal::from(1000);
+    }
+}
+
+/// Trades the inversion of FVGs: when a candle closes through an FVG, enters
+/// the other way at its edge, with the stop loss at the opposite edge.
+pub struct IFvg {
+    config: IFvgConfig,
+}
+
+impl Strategy for IFvg {
+    const NAME: &'static str = "ifvg";
+
+    type Config = IFvgConfig;
+
+    fn new(config: Self::Config) -> anyhow::Result<Self> {
+        return Ok(Self { config });
+    }
+
+    fn on_fvg(&mut self, context: &Context, fvg: &FVG) -> anyhow::Result<()> {
+        let close_time = match fvg.close_time() {
+            Some(close_time) => *close_time,
+            None => return Ok(()),
+        };
+        let flow = if fvg.flow() == "bear" { "bull" } else { "bear" };
+        let (entry, stop_loss) = if flow == "bull" {
+            (*fvg.high(), *fvg.low())
+        } else {
+            (*fvg.low(), *fvg.high())
+        };
+        let reward = self.config.risk_reward * (stop_loss - entry).abs();
+        let take_profit = if flow == "bull" {
+            entry + reward
+        } else {
+            entry - reward
+        };
+        let trade = TradeBuilder::default()
+            .pair(fvg.pair().to_owned())
+            .open_time(close_time)
+            .timeframe(fvg.timeframe().to_owned())
+            .fill_time(None)
+            .quantity(self.config.notional / entry)
+            .entry(entry)
+            .stop_loss(stop_loss)
+            .take_profit(take_profit)
+            .flow(flow.to_owned())
+            .close_time(None)
+            .close(None)
+            .build()?;
+
+        let trade = context.place_order(trade)?;
+        println!("new trade: {trade:#?}");
+        return Ok(());
+    }
+}
@@ -1,11 +1,53 @@
 pub mod algo_a_b;
 pub mod combo;
+pub mod context;
+pub mod ifvg;
 
-use async_trait::async_trait;
+use models::{fvg::FVG, swing::Swing, trade::Trade, Candle};
+use serde::de::DeserializeOwned;
 
-use crate::AppState;
+use self::context::Context;
 
-#[async_trait]
-pub trait Strategy {
-    async fn run(state: AppState) -> anyhow::Result<()>;
+/// A trading strategy, driven by the runner with the messages of its mode.
+///
+/// Every hook does nothing by default. Hooks returning an error are logged by
+/// the runner, which goes on with the next message.
+pub trait Strategy: Sized + Send + 'static {
+    /// Name of the strategy, and of its table in the strategy configuration.
+    const NAME: &'static str;
+
+    /// Parameters of the strategy.
+    type Config: DeserializeOwned + Clone + Send + 'static;
+
+    fn new(config: Self::Config) -> anyhow::Result<Self>;
+
+    /// Called before the first message, and after every backtest reset.
+    fn on_start(&mut self, _context: &Context) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
+    /// Called when the runner stops, and before every backtest reset.
+    fn on_stop(&mut self, _context: &Context) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
+    fn on_candle_close(&mut self, _context: &Context, _candle: &Candle) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
+    /// Called with new FVGs, and with FVGs closed by a candle, whose
+    /// `close_time` is set.
+    fn on_fvg(&mut self, _context: &Context, _fvg: &FVG) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
+    fn on_swing(&mut self, _context: &Context, _swing: &Swing) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
+    /// Called with every trade filled by position-manager, whichever
+    /// strategy placed it.
+    fn on_fill(&mut self, _context: &Context, _trade: &Trade) -> anyhow::Result<()> {
+        return Ok(());
+    }
 }
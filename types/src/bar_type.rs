@@ -0,0 +1,119 @@
+use rust_decimal::Decimal;
+use serde::{de::Visitor, Deserialize};
+
+use crate::Timeframe;
+
+/// Bars that are not aggregated on a fixed time period.
+///
+/// Bars are stored as candles whose timeframe is the bar type string, e.g.
+/// `renko-50`, so they are read like any other timeframe.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BarType {
+    /// Heikin-Ashi candles of a time frame.
+    HeikinAshi(Timeframe),
+    /// Bars closing once their high - low range exceeds the given price range.
+    Range(Decimal),
+    /// Bricks of the given price size.
+    Renko(Decimal),
+    /// Bars closing every given number of trades.
+    Tick(i64),
+    /// Bars closing once the given base volume was traded.
+    Volume(Decimal),
+}
+
+impl BarType {
+    /// Tag of regular time based candles.
+    pub const TIME_TAG: &'static str = "time";
+
+    /// Tag stored in the `bar_type` column of the candles of this bar type.
+    pub fn tag(&self) -> &'static str {
+        return match self {
+            BarType::HeikinAshi(_) => "heikin_ashi",
+            BarType::Range(_) => "range",
+            BarType::Renko(_) => "renko",
+            BarType::Tick(_) => "tick",
+            BarType::Volume(_) => "volume",
+        };
+    }
+
+    /// Whether the bars are built from trades, which backtests do not replay.
+    pub fn from_trades(&self) -> bool {
+        return !matches!(self, BarType::HeikinAshi(_));
+    }
+}
+
+impl std::fmt::Display for BarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            BarType::HeikinAshi(x) => write!(f, "ha-{x}"),
+            BarType::Range(x) => write!(f, "range-{x}"),
+            BarType::Renko(x) => write!(f, "renko-{x}"),
+            BarType::Tick(x) => write!(f, "tick-{x}"),
+            BarType::Volume(x) => write!(f, "volume-{x}"),
+        };
+    }
+}
+
+impl std::str::FromStr for BarType {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: &str| crate::Error::BarTypeError(format!("{s}: {reason}"));
+        let (kind, value) = s.split_once('-').ok_or_else(|| err("invalid format"))?;
+        let decimal = || -> Result<Decimal, Self::Err> {
+            let value = value.parse::<Decimal>().map_err(|x| err(&x.to_string()))?;
+            if value <= Decimal::ZERO {
+                return Err(err("size must be positive"));
+            }
+            return Ok(value);
+        };
+
+        let bar_type = match kind {
+            "ha" => BarType::HeikinAshi(
+                value
+                    .parse()
+                    .map_err(|x: crate::Error| err(&x.to_string()))?,
+            ),
+            "range" => BarType::Range(decimal()?),
+            "renko" => BarType::Renko(decimal()?),
+            "tick" => {
+                let value = value.parse::<i64>().map_err(|x| err(&x.to_string()))?;
+                if value <= 0 {
+                    return Err(err("size must be positive"));
+                }
+                BarType::Tick(value)
+            }
+            "volume" => BarType::Volume(decimal()?),
+            _ => return Err(err("invalid kind")),
+        };
+        return Ok(bar_type);
+    }
+}
+
+struct BarTypeVisitor;
+
+impl<'de> Visitor<'de> for BarTypeVisitor {
+    type Value = BarType;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return formatter.write_str(
+            "a string in the format 'ha-<timeframe>', 'range-x', 'renko-x', 'tick-x' or 'volume-x' where x is a positive number",
+        );
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        return v.parse().map_err(E::custom);
+    }
+}
+
+impl<'de> Deserialize<'de> for BarType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        return deserializer.deserialize_string(BarTypeVisitor);
+    }
+}
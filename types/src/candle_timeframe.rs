@@ -0,0 +1,81 @@
+use serde::{de::Visitor, Deserialize};
+
+use crate::{BarType, Timeframe};
+
+/// Time frame of a candle series: a fixed time period, e.g. `1h`, or a bar
+/// type, e.g. `ha-1h`, whose bars are stored with the bar type string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CandleTimeframe {
+    Time(Timeframe),
+    Bar(BarType),
+}
+
+impl std::fmt::Display for CandleTimeframe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            CandleTimeframe::Time(x) => write!(f, "{x}"),
+            CandleTimeframe::Bar(x) => write!(f, "{x}"),
+        };
+    }
+}
+
+impl std::str::FromStr for CandleTimeframe {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Bar types are the only ones with a `-`.
+        return match s.contains('-') {
+            true => Ok(CandleTimeframe::Bar(s.parse()?)),
+            false => Ok(CandleTimeframe::Time(s.parse()?)),
+        };
+    }
+}
+
+struct CandleTimeframeVisitor;
+
+impl<'de> Visitor<'de> for CandleTimeframeVisitor {
+    type Value = CandleTimeframe;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return formatter.write_str("a timeframe, e.g. '1h', or a bar type, e.g. 'ha-1h'");
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        return v.parse().map_err(E::custom);
+    }
+}
+
+impl<'de> Deserialize<'de> for CandleTimeframe {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        return deserializer.deserialize_string(CandleTimeframeVisitor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BarType, CandleTimeframe, Timeframe};
+
+    #[test]
+    fn parses_timeframes_and_bar_types() {
+        assert_eq!(
+            "1h".parse::<CandleTimeframe>().unwrap(),
+            CandleTimeframe::Time(Timeframe::Hour(1))
+        );
+        assert_eq!(
+            "ha-1h".parse::<CandleTimeframe>().unwrap(),
+            CandleTimeframe::Bar(BarType::HeikinAshi(Timeframe::Hour(1)))
+        );
+        assert_eq!(
+            "renko-50".parse::<CandleTimeframe>().unwrap().to_string(),
+            "renko-50"
+        );
+        assert!("ha-1x".parse::<CandleTimeframe>().is_err());
+        assert!("1x".parse::<CandleTimeframe>().is_err());
+    }
+}
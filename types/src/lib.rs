@@ -1,9 +1,15 @@
+pub mod bar_type;
+pub mod candle_timeframe;
 pub mod timeframe;
 
+pub use bar_type::BarType;
+pub use candle_timeframe::CandleTimeframe;
 pub use timeframe::Timeframe;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Invalid timeframe: {0}")]
     TimeframeError(String),
+    #[error("Invalid bar type: {0}")]
+    BarTypeError(String),
 }
@@ -3,7 +3,7 @@ use garde::Validate;
 use regex::Regex;
 use serde::{de::Visitor, Deserialize};
 
-#[derive(Debug, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Validate)]
 pub enum Timeframe {
     Month(#[garde(range(min = 1, max = 12))] i64),
     Week(#[garde(range(min = 1, max = 52))] i64),
@@ -25,6 +25,41 @@ impl std::fmt::Display for Timeframe {
 }
 
 impl Timeframe {
+    /// Nominal length of a candle, months counting as 31 days.
+    ///
+    /// Actual candles may be shorter, see `open_and_size`.
+    pub fn nominal_duration(&self) -> chrono::Duration {
+        return match self {
+            Timeframe::Month(x) => chrono::Duration::days(31 * x),
+            Timeframe::Week(x) => chrono::Duration::weeks(*x),
+            Timeframe::Day(x) => chrono::Duration::days(*x),
+            Timeframe::Hour(x) => chrono::Duration::hours(*x),
+            Timeframe::Minute(x) => chrono::Duration::minutes(*x),
+        };
+    }
+
+    /// Whether every candle of `self` falls within a single candle of `other`,
+    /// so `other` can be aggregated from `self`. Weeks never divide months.
+    pub fn divides(&self, other: &Timeframe) -> bool {
+        let intraday_minutes = |timeframe: &Timeframe| match timeframe {
+            Timeframe::Minute(x) => Some(*x),
+            Timeframe::Hour(x) => Some(x * 60),
+            _ => None,
+        };
+        return match (self, other) {
+            (Timeframe::Minute(_) | Timeframe::Hour(_), _) => match intraday_minutes(other) {
+                Some(minutes) => minutes % intraday_minutes(self).unwrap() == 0,
+                // Intraday candles restart every day.
+                None => true,
+            },
+            (Timeframe::Day(x), Timeframe::Day(y)) => y % x == 0,
+            (Timeframe::Day(x), Timeframe::Week(_) | Timeframe::Month(_)) => *x == 1,
+            (Timeframe::Week(x), Timeframe::Week(y)) => y % x == 0,
+            (Timeframe::Month(x), Timeframe::Month(y)) => y % x == 0,
+            _ => false,
+        };
+    }
+
     pub fn open_and_size(
         &self,
         date_time: &chrono::DateTime<chrono::Utc>,
@@ -35,17 +70,10 @@ impl Timeframe {
         let day = date_time.day();
         let candle_open = match self {
             Timeframe::Month(x) => {
+                let x = u32::try_from(*x)?;
                 let open = chrono::Utc
-                    .with_ymd_and_hms(
-                        year,
-                        1 + ((month - 1) / u32::try_from(*x).unwrap()),
-                        1,
-                        0,
-                        0,
-                        0,
-                    )
+                    .with_ymd_and_hms(year, 1 + ((month - 1) / x) * x, 1, 0, 0, 0)
                     .unwrap();
-                let x = u32::try_from(*x)?;
                 let next_open = if open.month() + x > 12 {
                     chrono::Utc
                         .with_ymd_and_hms(year + 1, 1, 1, 0, 0, 0)
@@ -139,6 +167,34 @@ impl Timeframe {
     }
 }
 
+impl std::str::FromStr for Timeframe {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: &str| crate::Error::TimeframeError(format!("{s}: {reason}"));
+        let regex = Regex::new(r"^(\d+)([MWDhm])$").map_err(|x| err(&x.to_string()))?;
+        let captures = regex.captures(s).ok_or_else(|| err("invalid format"))?;
+        let value_str = captures
+            .get(1)
+            .ok_or_else(|| err("missing number"))?
+            .as_str();
+        let unit = captures.get(2).ok_or_else(|| err("missing unit"))?.as_str();
+        let value = value_str.parse::<i64>().map_err(|x| err(&x.to_string()))?;
+
+        let timeframe = match unit {
+            "M" => Timeframe::Month(value),
+            "W" => Timeframe::Week(value),
+            "D" => Timeframe::Day(value),
+            "h" => Timeframe::Hour(value),
+            "m" => Timeframe::Minute(value),
+            _ => return Err(err("invalid unit")),
+        };
+
+        timeframe.validate(&()).map_err(|x| err(&x.to_string()))?;
+        return Ok(timeframe);
+    }
+}
+
 struct TimeframeVisitor;
 
 impl<'de> Visitor<'de> for TimeframeVisitor {
@@ -154,31 +210,7 @@ impl<'de> Visitor<'de> for TimeframeVisitor {
     where
         E: serde::de::Error,
     {
-        let regex = Regex::new(r"^(\d+)([MWDhm])$").map_err(E::custom)?;
-        let captures = regex
-            .captures(v)
-            .ok_or_else(|| E::custom("invalid format"))?;
-        let value_str = captures
-            .get(1)
-            .ok_or_else(|| E::custom("missing number"))?
-            .as_str();
-        let unit = captures
-            .get(2)
-            .ok_or_else(|| E::custom("missing unit"))?
-            .as_str();
-        let value = value_str.parse::<i64>().map_err(E::custom)?;
-
-        let timeframe = match unit {
-            "M" => Timeframe::Month(value),
-            "W" => Timeframe::Week(value),
-            "D" => Timeframe::Day(value),
-            "h" => Timeframe::Hour(value),
-            "m" => Timeframe::Minute(value),
-            _ => return Err(E::custom("invalid unit")),
-        };
-
-        timeframe.validate(&()).map_err(E::custom)?;
-        return Ok(timeframe);
+        return v.parse().map_err(E::custom);
     }
 }
 
@@ -265,6 +297,23 @@ mod tests {
             .expect_err("Timeframe::Month(> 12 should not be valid");
     }
 
+    #[test]
+    fn divides() {
+        assert!(Timeframe::Minute(5).divides(&Timeframe::Minute(15)));
+        assert!(!Timeframe::Minute(2).divides(&Timeframe::Minute(15)));
+        assert!(Timeframe::Minute(15).divides(&Timeframe::Hour(1)));
+        assert!(!Timeframe::Minute(7).divides(&Timeframe::Hour(1)));
+        assert!(Timeframe::Hour(1).divides(&Timeframe::Minute(120)));
+        assert!(Timeframe::Minute(7).divides(&Timeframe::Day(1)));
+        assert!(Timeframe::Hour(4).divides(&Timeframe::Month(1)));
+        assert!(Timeframe::Day(1).divides(&Timeframe::Week(1)));
+        assert!(!Timeframe::Day(2).divides(&Timeframe::Month(1)));
+        assert!(!Timeframe::Week(1).divides(&Timeframe::Month(1)));
+        assert!(Timeframe::Month(1).divides(&Timeframe::Month(3)));
+        assert!(!Timeframe::Month(2).divides(&Timeframe::Month(3)));
+        assert!(!Timeframe::Hour(1).divides(&Timeframe::Minute(15)));
+    }
+
     #[test]
     fn candle_open_minutes() {
         assert_eq!(
@@ -507,6 +556,16 @@ mod tests {
             ),
         );
 
+        assert_eq!(
+            Timeframe::Month(3)
+                .open_and_size(&chrono::Utc.with_ymd_and_hms(2024, 5, 17, 0, 0, 36).unwrap())
+                .unwrap(),
+            (
+                chrono::Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap(),
+                chrono::Duration::days(91).num_milliseconds()
+            ),
+        );
+
         assert_eq!(
             Timeframe::Month(12)
                 .open_and_size(